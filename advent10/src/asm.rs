@@ -0,0 +1,208 @@
+//! An assembler for CPU programs, so we can write programs with labels instead of counting out
+//! jump offsets by hand.
+//!
+//! A program has one instruction per line, in the same format that `CPUInstruction` displays.
+//! A line like `loop:` defines a label for the instruction after it, and `jmp` and `jnz` can
+//! jump to a label by name instead of by offset. Anything after a `;` is a comment.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::cpu::CPUInstruction;
+
+/// What went wrong on a line of assembly.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    BadInstruction(String),
+    BadLabel(String),
+    UndefinedLabel(String),
+    /// This label was already defined on the given (1-based) line.
+    DuplicateLabel(String, usize),
+}
+
+/// An error in a program, with the 1-based line number where it happened.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line_num: usize,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line_num)?;
+        match &self.kind {
+            AsmErrorKind::BadInstruction(text) => write!(f, "not an instruction: {:?}", text),
+            AsmErrorKind::BadLabel(name) => write!(f, "not a valid label name: {:?}", name),
+            AsmErrorKind::UndefinedLabel(name) => write!(f, "no such label: {}", name),
+            AsmErrorKind::DuplicateLabel(name, first) => {
+                write!(f, "label {} was already defined on line {}", name, first)
+            }
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+/// A line of the program with its comment and surrounding whitespace removed.
+enum SourceLine<'a> {
+    Blank,
+    Label(&'a str),
+    Instruction(&'a str),
+}
+
+fn classify(line: &str) -> SourceLine<'_> {
+    let code = match line.find(';') {
+        Some(idx) => &line[..idx],
+        None => line,
+    }
+    .trim();
+    if code.is_empty() {
+        SourceLine::Blank
+    } else if let Some(name) = code.strip_suffix(':') {
+        SourceLine::Label(name.trim())
+    } else {
+        SourceLine::Instruction(code)
+    }
+}
+
+fn is_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Turn the text of a program into instructions. The first pass finds where each label points,
+/// and the second pass parses each instruction, replacing label names with jump offsets.
+pub fn assemble(source: &str) -> Result<Vec<CPUInstruction>, AsmError> {
+    // first pass: labels, mapping their name to the instruction index and the line they're on
+    let mut labels: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut num_instructions: usize = 0;
+    for (idx, line) in source.lines().enumerate() {
+        let line_num = idx + 1;
+        match classify(line) {
+            SourceLine::Blank => {}
+            SourceLine::Instruction(_) => num_instructions += 1,
+            SourceLine::Label(name) => {
+                if !is_label_name(name) {
+                    let kind = AsmErrorKind::BadLabel(name.to_owned());
+                    return Err(AsmError { line_num, kind });
+                }
+                if let Some((_, first)) = labels.insert(name, (num_instructions, line_num)) {
+                    let kind = AsmErrorKind::DuplicateLabel(name.to_owned(), first);
+                    return Err(AsmError { line_num, kind });
+                }
+            }
+        }
+    }
+
+    // second pass: instructions
+    let mut program: Vec<CPUInstruction> = Vec::new();
+    for (idx, line) in source.lines().enumerate() {
+        let line_num = idx + 1;
+        let code = match classify(line) {
+            SourceLine::Instruction(code) => code,
+            _ => continue,
+        };
+        let mut words: Vec<String> = code.split_whitespace().map(|w| w.to_owned()).collect();
+        let last = words.len() - 1;
+        if (words[0] == "jmp" || words[0] == "jnz") && is_label_name(&words[last]) {
+            match labels.get(words[last].as_str()) {
+                Some((target, _)) => {
+                    words[last] = (*target as i64 - program.len() as i64).to_string();
+                }
+                None => {
+                    let kind = AsmErrorKind::UndefinedLabel(words[last].clone());
+                    return Err(AsmError { line_num, kind });
+                }
+            }
+        }
+        match words.join(" ").parse() {
+            Ok(inst) => program.push(inst),
+            Err(_) => {
+                let kind = AsmErrorKind::BadInstruction(code.to_owned());
+                return Err(AsmError { line_num, kind });
+            }
+        }
+    }
+    Ok(program)
+}
+
+/// Write a program back out as text, one instruction per line, with jumps as plain offsets.
+/// This is the same format that `parse_program` reads, and `assemble` accepts it too.
+pub fn disassemble(program: &[CPUInstruction]) -> String {
+    let lines: Vec<String> = program.iter().map(|inst| inst.to_string()).collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{parse_program, Cpu, Register};
+    use crate::TEST_INPUT;
+
+    const COUNTDOWN: &str = "; add 2 to X three times
+    set y 3
+loop:
+    addx 2
+    addy -1
+    jnz y loop  ; go around again
+    jmp end
+    addx 100
+end:
+    noop";
+
+    #[test]
+    fn test_assemble() {
+        let program = assemble(COUNTDOWN).unwrap();
+        assert_eq!(
+            program,
+            vec![
+                CPUInstruction::Set(Register::Y, 3),
+                CPUInstruction::AddX(2),
+                CPUInstruction::AddY(-1),
+                CPUInstruction::Jnz(Register::Y, -2),
+                CPUInstruction::Jmp(2),
+                CPUInstruction::AddX(100),
+                CPUInstruction::Noop,
+            ]
+        );
+        let mut cpu = Cpu::new(program);
+        cpu.by_ref().count();
+        assert_eq!(cpu.x(), 7);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let program = assemble(COUNTDOWN).unwrap();
+        let text = disassemble(&program);
+        assert_eq!(parse_program(&text).unwrap(), program);
+        assert_eq!(assemble(&text).unwrap(), program);
+
+        // the puzzle input is already in this format
+        assert_eq!(disassemble(&assemble(TEST_INPUT).unwrap()), TEST_INPUT);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            assemble("noop\naddx\n").unwrap_err().to_string(),
+            "line 2: not an instruction: \"addx\""
+        );
+        assert_eq!(
+            assemble("start:\nnoop\njmp finish").unwrap_err(),
+            AsmError { line_num: 3, kind: AsmErrorKind::UndefinedLabel("finish".to_owned()) }
+        );
+        assert_eq!(
+            assemble("here:\nnoop\n\nhere:\njmp here").unwrap_err().to_string(),
+            "line 4: label here was already defined on line 1"
+        );
+        assert_eq!(
+            assemble("2fast:").unwrap_err(),
+            AsmError { line_num: 1, kind: AsmErrorKind::BadLabel("2fast".to_owned()) }
+        );
+    }
+}
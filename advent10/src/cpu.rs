@@ -0,0 +1,271 @@
+//! A cycle-accurate model of the handheld device's CPU.
+use std::error::Error;
+use std::fmt;
+
+use parse_display::{Display, FromStr, ParseError};
+
+/// The CPU's registers. The CRT only looks at X; Y is scratch space, such as for loop counters.
+#[derive(Display, FromStr, PartialEq, Eq, Debug, Clone, Copy)]
+#[display(style = "lowercase")]
+pub enum Register {
+    X,
+    Y,
+}
+
+/// The instructions the CPU understands. The puzzle only uses `addx` and `noop`; the rest are
+/// there so we can write more interesting programs for the CRT. Jump offsets are relative to the
+/// jump instruction, counted in instructions, so `jmp 1` does nothing and `jmp 0` loops forever.
+#[derive(Display, FromStr, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum CPUInstruction {
+    #[display("addx {0}")]
+    AddX(i64),
+    #[display("noop")]
+    Noop,
+    #[display("addy {0}")]
+    AddY(i64),
+    #[display("mulx {0}")]
+    MulX(i64),
+    #[display("set {0} {1}")]
+    Set(Register, i64),
+    #[display("jmp {0}")]
+    Jmp(i64),
+    /// Jump if the register isn't zero.
+    #[display("jnz {0} {1}")]
+    Jnz(Register, i64),
+}
+
+impl CPUInstruction {
+    /// How many cycles this instruction takes to complete.
+    pub fn cycles(&self) -> i64 {
+        match self {
+            CPUInstruction::AddX(_) => 2,
+            CPUInstruction::Noop => 1,
+            CPUInstruction::AddY(_) => 2,
+            CPUInstruction::MulX(_) => 3,
+            CPUInstruction::Set(_, _) => 1,
+            CPUInstruction::Jmp(_) => 1,
+            CPUInstruction::Jnz(_, _) => 2,
+        }
+    }
+}
+
+/// Something a program did that the CPU can't do.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum CpuError {
+    /// The instruction at this index made a register or the jump target overflow.
+    Overflow { pc: usize, inst: CPUInstruction },
+    /// The program was still running after this many cycles, which is as long as we were
+    /// willing to wait. It might loop forever.
+    TooManyCycles(i64),
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::Overflow { pc, inst } => write!(f, "instruction {} ({}) overflowed", pc, inst),
+            CpuError::TooManyCycles(max_cycles) => {
+                write!(f, "still running after {} cycles", max_cycles)
+            }
+        }
+    }
+}
+
+impl Error for CpuError {}
+
+/// Parse a program, one instruction per line.
+pub fn parse_program(input: &str) -> Result<Vec<CPUInstruction>, ParseError> {
+    input.lines().map(|line| line.parse()).collect()
+}
+
+/// The CPU runs a program one cycle at a time. It's an iterator over the cycles it runs, yielding
+/// the cycle number (starting from 1) and the value of the X register *during* that cycle.
+#[derive(Debug, Clone)]
+pub struct Cpu {
+    program: Vec<CPUInstruction>,
+    /// The index of the instruction currently being executed.
+    pc: usize,
+    /// How many cycles of the current instruction have already run.
+    progress: i64,
+    /// The number of cycles that have completed.
+    cycle: i64,
+    x: i64,
+    y: i64,
+}
+
+impl Cpu {
+    pub fn new(program: Vec<CPUInstruction>) -> Self {
+        Cpu {
+            program,
+            pc: 0,
+            progress: 0,
+            cycle: 0,
+            x: 1,
+            y: 0,
+        }
+    }
+
+    /// The value of the X register right now, which is also its value during the next cycle.
+    pub fn x(&self) -> i64 {
+        self.x
+    }
+
+    /// The value of the Y register right now.
+    pub fn y(&self) -> i64 {
+        self.y
+    }
+
+    /// The number of cycles that have completed so far.
+    pub fn elapsed(&self) -> i64 {
+        self.cycle
+    }
+
+    /// The index of the instruction that's running or about to run.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// How many cycles of the current instruction have already run.
+    pub fn progress(&self) -> i64 {
+        self.progress
+    }
+
+    /// The instruction that's running or about to run, or None if the program has finished.
+    pub fn current_instruction(&self) -> Option<CPUInstruction> {
+        self.program.get(self.pc).copied()
+    }
+
+    /// Run a single cycle, returning the cycle number and the value of X during that cycle, or
+    /// None if the program has finished. If the instruction can't finish, because it overflows,
+    /// this returns an error and the CPU stays where it was, so it'll fail the same way again.
+    pub fn try_step(&mut self) -> Result<Option<(i64, i64)>, CpuError> {
+        let inst = match self.program.get(self.pc) {
+            Some(inst) => *inst,
+            None => return Ok(None),
+        };
+        let during = (self.cycle + 1, self.x);
+        if self.progress + 1 >= inst.cycles() {
+            self.execute(inst)?;
+            self.progress = 0;
+        } else {
+            self.progress += 1;
+        }
+        self.cycle += 1;
+        Ok(Some(during))
+    }
+
+    /// Carry out the effect of an instruction, which happens after its last cycle, and move on
+    /// to the next instruction. Jumping outside the program ends it. Nothing changes if the
+    /// instruction overflows.
+    fn execute(&mut self, inst: CPUInstruction) -> Result<(), CpuError> {
+        let overflow = || CpuError::Overflow { pc: self.pc, inst };
+        let (mut x, mut y) = (self.x, self.y);
+        let mut offset: i64 = 1;
+        match inst {
+            CPUInstruction::AddX(val) => x = x.checked_add(val).ok_or_else(overflow)?,
+            CPUInstruction::Noop => {}
+            CPUInstruction::AddY(val) => y = y.checked_add(val).ok_or_else(overflow)?,
+            CPUInstruction::MulX(val) => x = x.checked_mul(val).ok_or_else(overflow)?,
+            CPUInstruction::Set(Register::X, val) => x = val,
+            CPUInstruction::Set(Register::Y, val) => y = val,
+            CPUInstruction::Jmp(jump) => offset = jump,
+            CPUInstruction::Jnz(reg, jump) => {
+                let value = match reg {
+                    Register::X => x,
+                    Register::Y => y,
+                };
+                if value != 0 {
+                    offset = jump;
+                }
+            }
+        }
+        let target = i64::try_from(self.pc).ok().and_then(|pc| pc.checked_add(offset)).ok_or_else(overflow)?;
+        // a negative target is outside the program too
+        self.pc = usize::try_from(target).unwrap_or(self.program.len());
+        self.x = x;
+        self.y = y;
+        Ok(())
+    }
+}
+
+/// Runs the program one cycle at a time, giving the cycle number and the value of X during it.
+/// This stops at the first error as well as at the end of the program, so it's only for
+/// programs that can't overflow, or for callers that check afterward: `try_step` returns the
+/// same error again if there was one, or `Ok(None)` if the program finished.
+impl Iterator for Cpu {
+    type Item = (i64, i64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_step().ok().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cycles() {
+        let program = parse_program("noop\naddx 3\naddx -5").unwrap();
+        let cycles: Vec<(i64, i64)> = Cpu::new(program).collect();
+        assert_eq!(cycles, vec![(1, 1), (2, 1), (3, 1), (4, 4), (5, 4)]);
+    }
+
+    #[test]
+    fn test_state_between_cycles() {
+        let mut cpu = Cpu::new(parse_program("noop\naddx 3\naddx -5").unwrap());
+        cpu.try_step().unwrap();
+        cpu.try_step().unwrap();
+        assert_eq!(cpu.elapsed(), 2);
+        assert_eq!(cpu.pc(), 1);
+        assert_eq!(cpu.progress(), 1);
+        assert_eq!(cpu.current_instruction(), Some(CPUInstruction::AddX(3)));
+        while cpu.try_step().unwrap().is_some() {}
+        assert_eq!(cpu.elapsed(), 5);
+        assert_eq!(cpu.x(), -1);
+        assert_eq!(cpu.current_instruction(), None);
+    }
+
+    #[test]
+    fn test_loop() {
+        // count Y down from 3, adding 2 to X each time, then multiply X by 5 and jump away
+        let program = parse_program("set y 3\naddx 2\naddy -1\njnz y -2\nmulx 5\njmp -10").unwrap();
+        let mut cpu = Cpu::new(program);
+        let cycles: Vec<(i64, i64)> = cpu.by_ref().collect();
+        // 1 cycle for set, 3 loops of 6 cycles, 3 for mulx and 1 for jmp
+        assert_eq!(cycles.len(), 1 + 3 * 6 + 3 + 1);
+        assert_eq!(cycles[3], (4, 3));
+        assert_eq!(cpu.x(), 35);
+        assert_eq!(cpu.y(), 0);
+        assert_eq!(cpu.current_instruction(), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut cpu = Cpu::new(parse_program("set x 9223372036854775807\nmulx 2\nnoop").unwrap());
+        assert_eq!(cpu.try_step(), Ok(Some((1, 1))));
+        assert_eq!(cpu.try_step(), Ok(Some((2, i64::MAX))));
+        assert_eq!(cpu.try_step(), Ok(Some((3, i64::MAX))));
+        let overflow = CpuError::Overflow { pc: 1, inst: CPUInstruction::MulX(2) };
+        assert_eq!(cpu.try_step(), Err(overflow));
+        // nothing changed, so it fails again
+        assert_eq!((cpu.elapsed(), cpu.pc(), cpu.progress(), cpu.x()), (3, 1, 2, i64::MAX));
+        assert_eq!(cpu.try_step(), Err(overflow));
+        // the iterator just stops, and try_step still says why
+        assert_eq!(cpu.next(), None);
+        assert_eq!(cpu.try_step(), Err(overflow));
+        assert_eq!(overflow.to_string(), "instruction 1 (mulx 2) overflowed");
+
+        for program in ["set x -9223372036854775808\naddx -1", "addy 9223372036854775807\naddy 1"] {
+            let mut cpu = Cpu::new(parse_program(program).unwrap());
+            assert!(cpu.by_ref().count() < 4);
+            assert!(matches!(cpu.try_step(), Err(CpuError::Overflow { pc: 1, inst: _ })));
+        }
+        let mut cpu = Cpu::new(parse_program("noop\njmp 9223372036854775807").unwrap());
+        assert_eq!(cpu.by_ref().count(), 1);
+        assert_eq!(cpu.try_step(), Err(CpuError::Overflow { pc: 1, inst: CPUInstruction::Jmp(i64::MAX) }));
+        // a jump that doesn't overflow, but goes far away, just ends the program
+        let mut cpu = Cpu::new(parse_program("jmp 9223372036854775806").unwrap());
+        assert_eq!(cpu.by_ref().count(), 1);
+        assert_eq!(cpu.try_step(), Ok(None));
+    }
+}
@@ -0,0 +1,234 @@
+//! The CRT screen on the handheld device, and a way to read the letters it shows.
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cpu::Cpu;
+
+/// The height of a capital letter in the font the elves use, in pixels.
+const GLYPH_HEIGHT: usize = 6;
+
+/// The width of a capital letter. Letters are separated by one blank column.
+const GLYPH_WIDTH: usize = 4;
+
+/// The letters we know how to read, drawn the way they appear on the screen.
+const GLYPHS: &[(char, &str)] = &[
+    ('A', ".##.\n#..#\n#..#\n####\n#..#\n#..#"),
+    ('B', "###.\n#..#\n###.\n#..#\n#..#\n###."),
+    ('C', ".##.\n#..#\n#...\n#...\n#..#\n.##."),
+    ('E', "####\n#...\n###.\n#...\n#...\n####"),
+    ('F', "####\n#...\n###.\n#...\n#...\n#..."),
+    ('G', ".##.\n#..#\n#...\n#.##\n#..#\n.###"),
+    ('H', "#..#\n#..#\n####\n#..#\n#..#\n#..#"),
+    ('I', ".###\n..#.\n..#.\n..#.\n..#.\n.###"),
+    ('J', "..##\n...#\n...#\n...#\n#..#\n.##."),
+    ('K', "#..#\n#.#.\n##..\n#.#.\n#.#.\n#..#"),
+    ('L', "#...\n#...\n#...\n#...\n#...\n####"),
+    ('O', ".##.\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('P', "###.\n#..#\n#..#\n###.\n#...\n#..."),
+    ('R', "###.\n#..#\n#..#\n###.\n#.#.\n#..#"),
+    ('S', ".###\n#...\n#...\n.##.\n...#\n###."),
+    ('U', "#..#\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('Z', "####\n...#\n..#.\n.#..\n#...\n####"),
+];
+
+/// Things that can go wrong when reading a picture of the screen, or reading letters off of it.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CrtError {
+    /// A picture of the screen contained something other than `#` and `.`.
+    BadPixel { row: usize, col: usize, ch: char },
+    /// The rows of a picture of the screen weren't all the same width.
+    RaggedRow { row: usize, width: usize, expected: usize },
+    /// We can only read letters off a screen that's exactly one letter tall.
+    WrongHeight(usize),
+    /// The letter at this position doesn't look like anything we know.
+    UnknownGlyph { position: usize, glyph: String },
+}
+
+impl fmt::Display for CrtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrtError::BadPixel { row, col, ch } => {
+                write!(f, "unexpected pixel {:?} at row {}, column {}", ch, row, col)
+            }
+            CrtError::RaggedRow { row, width, expected } => {
+                write!(f, "row {} has width {}, expected {}", row, width, expected)
+            }
+            CrtError::WrongHeight(height) => {
+                write!(f, "can only read letters {} pixels tall, not {}", GLYPH_HEIGHT, height)
+            }
+            CrtError::UnknownGlyph { position, glyph } => {
+                write!(f, "don't recognize the letter at position {}:\n{}", position, glyph)
+            }
+        }
+    }
+}
+
+impl Error for CrtError {}
+
+/// The pixels on the CRT screen, which are either lit or dark.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Framebuffer {
+    /// A dark screen of the given size.
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+
+    /// Run a program on the CPU, drawing one pixel per cycle until the program ends or the
+    /// screen is full. A pixel is lit when the three-pixel-wide sprite, centered on the X
+    /// register, overlaps the pixel being drawn.
+    pub fn render(cpu: Cpu, width: usize, height: usize) -> Self {
+        let mut screen = Framebuffer::new(width, height);
+        for (cycle, register) in cpu.take(width * height) {
+            let pos = (cycle - 1) as usize;
+            let col = (pos % width) as i64;
+            screen.pixels[pos] = (register - col).abs() <= 1;
+        }
+        screen
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, col: usize, row: usize) -> bool {
+        self.pixels[row * self.width + col]
+    }
+
+    /// Draw one letter-sized chunk of the screen, starting at the given column, the same way
+    /// the glyphs in GLYPHS are drawn.
+    fn glyph_at(&self, left: usize) -> String {
+        let rows: Vec<String> = (0..self.height)
+            .map(|row| {
+                (left..left + GLYPH_WIDTH)
+                    .map(|col| if col < self.width && self.get(col, row) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect();
+        rows.join("\n")
+    }
+
+    /// Read the capital letters shown on the screen.
+    pub fn ocr(&self) -> Result<String, CrtError> {
+        if self.height != GLYPH_HEIGHT {
+            return Err(CrtError::WrongHeight(self.height));
+        }
+        let mut text = String::new();
+        for (position, left) in (0..self.width).step_by(GLYPH_WIDTH + 1).enumerate() {
+            let glyph = self.glyph_at(left);
+            match GLYPHS.iter().find(|(_, art)| *art == glyph) {
+                Some((letter, _)) => text.push(*letter),
+                None => return Err(CrtError::UnknownGlyph { position, glyph }),
+            }
+        }
+        Ok(text)
+    }
+}
+
+impl fmt::Display for Framebuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in 0..self.height {
+            if row > 0 {
+                writeln!(f)?;
+            }
+            for col in 0..self.width {
+                write!(f, "{}", if self.get(col, row) { '#' } else { '.' })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Framebuffer {
+    type Err = CrtError;
+
+    /// Read a picture of the screen in the same `#` and `.` format that Display writes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut pixels = Vec::new();
+        let mut width = 0;
+        let mut height = 0;
+        for (row, line) in s.lines().enumerate() {
+            let row_width = line.chars().count();
+            if row == 0 {
+                width = row_width;
+            } else if row_width != width {
+                return Err(CrtError::RaggedRow { row, width: row_width, expected: width });
+            }
+            for (col, ch) in line.chars().enumerate() {
+                match ch {
+                    '#' => pixels.push(true),
+                    '.' => pixels.push(false),
+                    _ => return Err(CrtError::BadPixel { row, col, ch }),
+                }
+            }
+            height += 1;
+        }
+        Ok(Framebuffer { width, height, pixels })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HI: &str = "#..#..###.
+#..#...#..
+####...#..
+#..#...#..
+#..#...#..
+#..#..###.";
+
+    #[test]
+    fn test_round_trip() {
+        let screen: Framebuffer = HI.parse().unwrap();
+        assert_eq!(screen.width(), 10);
+        assert_eq!(screen.height(), 6);
+        assert_eq!(screen.to_string(), HI);
+    }
+
+    #[test]
+    fn test_ocr() {
+        let screen: Framebuffer = HI.parse().unwrap();
+        assert_eq!(screen.ocr(), Ok("HI".to_owned()));
+    }
+
+    #[test]
+    fn test_unknown_glyph() {
+        let screen: Framebuffer = "#...\n.#..\n..#.\n...#\n..#.\n.#..".parse().unwrap();
+        assert_eq!(
+            screen.ocr(),
+            Err(CrtError::UnknownGlyph {
+                position: 0,
+                glyph: "#...\n.#..\n..#.\n...#\n..#.\n.#..".to_owned()
+            })
+        );
+        let short: Framebuffer = "####\n#...".parse().unwrap();
+        assert_eq!(short.ocr(), Err(CrtError::WrongHeight(2)));
+    }
+
+    #[test]
+    fn test_bad_picture() {
+        assert_eq!(
+            "##\n#".parse::<Framebuffer>(),
+            Err(CrtError::RaggedRow { row: 1, width: 1, expected: 2 })
+        );
+        assert_eq!(
+            "#x".parse::<Framebuffer>(),
+            Err(CrtError::BadPixel { row: 0, col: 1, ch: 'x' })
+        );
+    }
+}
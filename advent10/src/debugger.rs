@@ -0,0 +1,507 @@
+//! A step debugger for the CPU, for finding out why the signal strength came out wrong.
+//!
+//! The debugger reads commands one per line, either interactively or from a script:
+//!
+//! - `step`, `step N`: run one cycle, or N cycles
+//! - `next`, `next N`: run until the current instruction (or the next N instructions) finish
+//! - `continue`: run until a breakpoint is hit or the program finishes
+//! - `break cycle N`: stop before cycle N runs
+//! - `break pc N`: stop before the instruction at index N starts
+//! - `break x N`: stop when the X register changes to N
+//! - `delete N`: remove the breakpoint numbered N
+//! - `watch NAME`: show the value of `x`, `y`, `cycle`, `pc` or `signal` whenever execution
+//!   stops. Only these names can be watched, not expressions built from them.
+//! - `print`: show the state of the CPU
+//! - `sprite`: show where the sprite is, and where the CRT is about to draw
+//! - `quit`
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use parse_display::{Display, FromStr};
+
+use crate::cpu::Cpu;
+
+/// A condition for the debugger to stop on.
+#[derive(Display, FromStr, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Breakpoint {
+    /// Stop before this cycle runs, so that X is the value it will have during the cycle.
+    #[display("cycle {0}")]
+    Cycle(i64),
+    /// Stop before the instruction with this index starts.
+    #[display("pc {0}")]
+    Instruction(usize),
+    /// Stop when the X register changes to this value.
+    #[display("x {0}")]
+    X(i64),
+}
+
+/// A value to show every time execution stops.
+#[derive(Display, FromStr, PartialEq, Eq, Debug, Clone, Copy)]
+#[display(style = "lowercase")]
+pub enum Watch {
+    X,
+    Y,
+    Cycle,
+    Pc,
+    Signal,
+}
+
+#[derive(Display, FromStr, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Command {
+    #[display("step")]
+    Step,
+    #[display("step {0}")]
+    StepCycles(u64),
+    #[display("next")]
+    Next,
+    #[display("next {0}")]
+    NextInstructions(u64),
+    #[display("continue")]
+    Continue,
+    #[display("break {0}")]
+    Break(Breakpoint),
+    #[display("delete {0}")]
+    Delete(usize),
+    #[display("watch {0}")]
+    Watch(Watch),
+    #[display("print")]
+    Print,
+    #[display("sprite")]
+    Sprite,
+    #[display("quit")]
+    Quit,
+}
+
+/// An error in a debugger script, with the 1-based line number where it happened.
+#[derive(Debug)]
+pub enum DebugError {
+    BadCommand { line_num: usize, line: String },
+    NoSuchBreakpoint { line_num: usize, index: usize },
+    /// A count for `step` or `next` that's too big to keep track of.
+    CountTooBig { line_num: usize, count: u64 },
+    Io(io::Error),
+}
+
+impl fmt::Display for DebugError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DebugError::BadCommand { line_num, line } => {
+                write!(f, "line {}: don't understand the command {:?}", line_num, line)
+            }
+            DebugError::NoSuchBreakpoint { line_num, index } => {
+                write!(f, "line {}: there's no breakpoint {}", line_num, index)
+            }
+            DebugError::CountTooBig { line_num, count } => {
+                write!(f, "line {}: can't run {} steps", line_num, count)
+            }
+            DebugError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for DebugError {}
+
+impl From<io::Error> for DebugError {
+    fn from(err: io::Error) -> Self {
+        DebugError::Io(err)
+    }
+}
+
+/// The debugger wraps a CPU, and writes what it sees to `out`.
+pub struct Debugger<W: Write> {
+    cpu: Cpu,
+    /// Breakpoints, numbered from 1 in the order they were added. Deleted ones become None so
+    /// the others keep their numbers.
+    breakpoints: Vec<Option<Breakpoint>>,
+    watches: Vec<Watch>,
+    /// The cycle count when we last stopped at a breakpoint, so that we don't stop there again
+    /// as soon as we carry on.
+    stopped_at: Option<i64>,
+    /// The width of a row on the CRT, for showing the sprite.
+    row_size: i64,
+    out: W,
+}
+
+impl<W: Write> Debugger<W> {
+    pub fn new(cpu: Cpu, row_size: i64, out: W) -> Self {
+        Debugger {
+            cpu,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            stopped_at: None,
+            row_size,
+            out,
+        }
+    }
+
+    /// Get back the output, which is useful when it's a buffer.
+    #[cfg(test)]
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    /// The number of the first breakpoint that says to stop where the CPU is now. `x_before`
+    /// is the value X had before the cycle that just ran, or None if no cycle has run yet.
+    fn breakpoint_hit(&self, x_before: Option<i64>) -> Option<usize> {
+        let hit = self.breakpoints.iter().position(|bp| match bp {
+            Some(Breakpoint::Cycle(cycle)) => self.cpu.elapsed() + 1 == *cycle,
+            Some(Breakpoint::Instruction(pc)) => {
+                self.cpu.progress() == 0 && self.cpu.pc() == *pc
+            }
+            Some(Breakpoint::X(x)) => {
+                x_before.is_some_and(|before| before != *x) && self.cpu.x() == *x
+            }
+            None => false,
+        });
+        hit.map(|idx| idx + 1)
+    }
+
+    fn report_breakpoint(&mut self, number: usize) -> io::Result<()> {
+        self.stopped_at = Some(self.cpu.elapsed());
+        let breakpoint = self.breakpoints[number - 1].unwrap();
+        writeln!(self.out, "stopped at breakpoint {} ({})", number, breakpoint)
+    }
+
+    /// Run cycles until `done` says to stop, a breakpoint is hit, or the program finishes.
+    /// `done` is checked after each cycle.
+    fn run_until(&mut self, mut done: impl FnMut(&Cpu) -> bool) -> io::Result<()> {
+        // a breakpoint like `break cycle 1` says to stop before anything has run, unless we
+        // already stopped here, in which case we're carrying on past it
+        if self.cpu.current_instruction().is_some() && self.stopped_at != Some(self.cpu.elapsed()) {
+            if let Some(number) = self.breakpoint_hit(None) {
+                return self.report_breakpoint(number);
+            }
+        }
+        loop {
+            let x_before = self.cpu.x();
+            match self.cpu.try_step() {
+                Ok(None) => {
+                    return writeln!(
+                        self.out,
+                        "program finished after {} cycles",
+                        self.cpu.elapsed()
+                    );
+                }
+                Err(err) => return writeln!(self.out, "program crashed: {}", err),
+                Ok(Some(_)) => {
+                    if let Some(number) = self.breakpoint_hit(Some(x_before)) {
+                        return self.report_breakpoint(number);
+                    }
+                    if done(&self.cpu) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    fn watch_value(&self, watch: Watch) -> i64 {
+        match watch {
+            Watch::X => self.cpu.x(),
+            Watch::Y => self.cpu.y(),
+            Watch::Cycle => self.cpu.elapsed() + 1,
+            Watch::Pc => self.cpu.pc() as i64,
+            Watch::Signal => (self.cpu.elapsed() + 1) * self.cpu.x(),
+        }
+    }
+
+    /// Describe where the CPU is, followed by the values of anything being watched.
+    fn print_state(&mut self) -> io::Result<()> {
+        match self.cpu.current_instruction() {
+            Some(inst) => writeln!(
+                self.out,
+                "before cycle {}: X = {}, pc = {} ({}, cycle {} of {})",
+                self.cpu.elapsed() + 1,
+                self.cpu.x(),
+                self.cpu.pc(),
+                inst,
+                self.cpu.progress() + 1,
+                inst.cycles()
+            )?,
+            None => writeln!(
+                self.out,
+                "finished after {} cycles: X = {}",
+                self.cpu.elapsed(),
+                self.cpu.x()
+            )?,
+        }
+        for idx in 0..self.watches.len() {
+            let watch = self.watches[idx];
+            let value = self.watch_value(watch);
+            writeln!(self.out, "  {} = {}", watch, value)?;
+        }
+        Ok(())
+    }
+
+    /// Show the three pixels covered by the sprite, with a caret under the pixel that the CRT
+    /// draws during the next cycle.
+    fn print_sprite(&mut self) -> io::Result<()> {
+        let sprite: String = (0..self.row_size)
+            .map(|col| if (self.cpu.x() - col).abs() <= 1 { '#' } else { '.' })
+            .collect();
+        let beam = (self.cpu.elapsed() % self.row_size) as usize;
+        writeln!(self.out, "{}", sprite)?;
+        writeln!(self.out, "{}^", " ".repeat(beam))
+    }
+
+    /// Carry out a single command. Returns false if it's time to quit.
+    pub fn execute(&mut self, command: Command, line_num: usize) -> Result<bool, DebugError> {
+        match command {
+            Command::Step => self.execute(Command::StepCycles(1), line_num),
+            Command::Next => self.execute(Command::NextInstructions(1), line_num),
+            Command::StepCycles(count) => {
+                let target = i64::try_from(count)
+                    .ok()
+                    .and_then(|count| self.cpu.elapsed().checked_add(count))
+                    .ok_or(DebugError::CountTooBig { line_num, count })?;
+                self.run_until(|cpu| cpu.elapsed() >= target)?;
+                self.print_state()?;
+                Ok(true)
+            }
+            Command::NextInstructions(count) => {
+                // an instruction has finished when the CPU is at the start of the next one.
+                // Jumps mean that can be anywhere, so count them instead of looking at pc.
+                let mut finished: u64 = 0;
+                self.run_until(|cpu| {
+                    if cpu.progress() == 0 {
+                        finished += 1;
+                    }
+                    finished >= count
+                })?;
+                self.print_state()?;
+                Ok(true)
+            }
+            Command::Continue => {
+                self.run_until(|_| false)?;
+                self.print_state()?;
+                Ok(true)
+            }
+            Command::Break(breakpoint) => {
+                self.breakpoints.push(Some(breakpoint));
+                writeln!(self.out, "breakpoint {}: {}", self.breakpoints.len(), breakpoint)?;
+                Ok(true)
+            }
+            Command::Delete(index) => {
+                match self.breakpoints.get_mut(index.wrapping_sub(1)) {
+                    Some(slot @ Some(_)) => *slot = None,
+                    _ => return Err(DebugError::NoSuchBreakpoint { line_num, index }),
+                }
+                Ok(true)
+            }
+            Command::Watch(watch) => {
+                self.watches.push(watch);
+                Ok(true)
+            }
+            Command::Print => {
+                self.print_state()?;
+                Ok(true)
+            }
+            Command::Sprite => {
+                self.print_sprite()?;
+                Ok(true)
+            }
+            Command::Quit => Ok(false),
+        }
+    }
+
+    /// Run a script of debugger commands. Blank lines and lines starting with `#` are skipped.
+    /// Stops at the first command that doesn't make sense.
+    pub fn run_script(&mut self, script: &str) -> Result<(), DebugError> {
+        for (idx, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let command: Command = line.parse().map_err(|_| DebugError::BadCommand {
+                line_num: idx + 1,
+                line: line.to_owned(),
+            })?;
+            if !self.execute(command, idx + 1)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read commands from a terminal, showing a prompt, until `quit` or the end of input.
+    /// Mistakes are reported and don't end the session.
+    pub fn run_interactive(&mut self, input: impl BufRead) -> Result<(), DebugError> {
+        write!(self.out, "(debug) ")?;
+        self.out.flush()?;
+        for (idx, line) in input.lines().enumerate() {
+            let line = line?;
+            let result = match line.trim().parse::<Command>() {
+                Ok(command) => self.execute(command, idx + 1),
+                Err(_) if line.trim().is_empty() => Ok(true),
+                Err(_) => Err(DebugError::BadCommand { line_num: idx + 1, line }),
+            };
+            match result {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(DebugError::Io(err)) => return Err(DebugError::Io(err)),
+                Err(err) => writeln!(self.out, "{}", err)?,
+            }
+            write!(self.out, "(debug) ")?;
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::parse_program;
+    use crate::TEST_INPUT;
+
+    fn debug_script(program: &str, script: &str) -> String {
+        let cpu = Cpu::new(parse_program(program).unwrap());
+        let mut debugger = Debugger::new(cpu, 40, Vec::new());
+        debugger.run_script(script).unwrap();
+        String::from_utf8(debugger.into_output()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!("step".parse::<Command>().unwrap(), Command::Step);
+        assert_eq!("step 5".parse::<Command>().unwrap(), Command::StepCycles(5));
+        assert_eq!(
+            "break x -3".parse::<Command>().unwrap(),
+            Command::Break(Breakpoint::X(-3))
+        );
+        assert_eq!("watch signal".parse::<Command>().unwrap(), Command::Watch(Watch::Signal));
+    }
+
+    #[test]
+    fn test_break_on_cycle() {
+        // the puzzle says X is 21 during the 20th cycle
+        let output = debug_script(TEST_INPUT, "break cycle 20\nwatch signal\ncontinue");
+        assert_eq!(
+            output,
+            "breakpoint 1: cycle 20
+stopped at breakpoint 1 (cycle 20)
+before cycle 20: X = 21, pc = 10 (addx -1, cycle 1 of 2)
+  signal = 420
+"
+        );
+    }
+
+    #[test]
+    fn test_stepping() {
+        let output = debug_script("noop\naddx 3\naddx -5", "step\nnext\nstep 1\nprint\ncontinue");
+        assert_eq!(
+            output,
+            "before cycle 2: X = 1, pc = 1 (addx 3, cycle 1 of 2)
+before cycle 4: X = 4, pc = 2 (addx -5, cycle 1 of 2)
+before cycle 5: X = 4, pc = 2 (addx -5, cycle 2 of 2)
+before cycle 5: X = 4, pc = 2 (addx -5, cycle 2 of 2)
+program finished after 5 cycles
+finished after 5 cycles: X = -1
+"
+        );
+    }
+
+    #[test]
+    fn test_break_on_pc_and_x() {
+        let script = "break pc 2\nbreak x -1\ncontinue\nsprite\ndelete 1\ncontinue";
+        let output = debug_script("noop\naddx 3\naddx -5\nnoop", script);
+        assert_eq!(
+            output,
+            "breakpoint 1: pc 2
+breakpoint 2: x -1
+stopped at breakpoint 1 (pc 2)
+before cycle 4: X = 4, pc = 2 (addx -5, cycle 1 of 2)
+...###..................................
+   ^
+stopped at breakpoint 2 (x -1)
+before cycle 6: X = -1, pc = 3 (noop, cycle 1 of 1)
+"
+        );
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let cpu = Cpu::new(parse_program(TEST_INPUT).unwrap());
+        let mut debugger = Debugger::new(cpu, 40, Vec::new());
+        let err = debugger.run_script("step\n\nfly away").unwrap_err();
+        assert_eq!(err.to_string(), "line 3: don't understand the command \"fly away\"");
+        let err = debugger.run_script("delete 1").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: there's no breakpoint 1");
+        // counts can't be negative
+        let err = debugger.run_script("next -1").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: don't understand the command \"next -1\"");
+        assert!(debugger.run_script("step -1").is_err());
+        let err = debugger.run_script("step 18446744073709551615").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: can't run 18446744073709551615 steps");
+        // this many instructions just runs the whole program
+        assert!(debugger.run_script("next 18446744073709551615").is_ok());
+        // only the names can be watched
+        let err = debugger.run_script("watch x + 1").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: don't understand the command \"watch x + 1\"");
+        assert!(debugger.run_script("watch z").is_err());
+    }
+
+    #[test]
+    fn test_break_before_starting() {
+        let output = debug_script("noop\naddx 3\naddx -5", "break cycle 1\ncontinue\ncontinue\ncontinue");
+        assert_eq!(
+            output,
+            "breakpoint 1: cycle 1
+stopped at breakpoint 1 (cycle 1)
+before cycle 1: X = 1, pc = 0 (noop, cycle 1 of 1)
+program finished after 5 cycles
+finished after 5 cycles: X = -1
+program finished after 5 cycles
+finished after 5 cycles: X = -1
+"
+        );
+        let output = debug_script("noop\naddx 3", "break pc 0\nstep\nstep");
+        assert_eq!(
+            output,
+            "breakpoint 1: pc 0
+stopped at breakpoint 1 (pc 0)
+before cycle 1: X = 1, pc = 0 (noop, cycle 1 of 1)
+before cycle 2: X = 1, pc = 1 (addx 3, cycle 1 of 2)
+"
+        );
+    }
+
+    #[test]
+    fn test_interactive() {
+        let cpu = Cpu::new(parse_program("noop").unwrap());
+        let mut debugger = Debugger::new(cpu, 40, Vec::new());
+        debugger.run_interactive("oops\nquit\nstep\n".as_bytes()).unwrap();
+        assert_eq!(
+            String::from_utf8(debugger.into_output()).unwrap(),
+            "(debug) line 1: don't understand the command \"oops\"\n(debug) "
+        );
+    }
+
+    #[test]
+    fn test_next_over_jumps() {
+        // set, then addy and jnz back to it twice
+        let output = debug_script("set y 3\naddy -1\njnz y -1\nnoop", "watch y\nnext 4\nnext 2");
+        assert_eq!(
+            output,
+            "before cycle 8: X = 1, pc = 2 (jnz y -1, cycle 1 of 2)
+  y = 1
+before cycle 12: X = 1, pc = 2 (jnz y -1, cycle 1 of 2)
+  y = 0
+"
+        );
+    }
+
+    #[test]
+    fn test_crash() {
+        let output = debug_script("set x 9223372036854775807\naddx 1\nnoop", "continue\nnext");
+        assert_eq!(
+            output,
+            "program crashed: instruction 1 (addx 1) overflowed
+before cycle 3: X = 9223372036854775807, pc = 1 (addx 1, cycle 2 of 2)
+program crashed: instruction 1 (addx 1) overflowed
+before cycle 3: X = 9223372036854775807, pc = 1 (addx 1, cycle 2 of 2)
+"
+        );
+    }
+}
@@ -1,6 +1,18 @@
 // An example to build from each day
+use std::env;
+use std::error::Error;
 use std::fs;
-use parse_display::{Display, FromStr};
+use std::io;
+
+mod asm;
+mod cpu;
+mod crt;
+mod debugger;
+mod synth;
+mod trace;
+use cpu::{parse_program, Cpu};
+use crt::Framebuffer;
+use debugger::Debugger;
 
 pub const SMALL_EXAMPLE: &str = "noop
 addx 3
@@ -159,89 +171,95 @@ noop
 noop
 noop";
 
-#[derive(Display, FromStr, PartialEq, Debug)]
-enum CPUInstruction {
-    #[display("addx {0}")]
-    AddX(i64),
-    #[display("noop")]
-    Noop,
+/// Add up the "signal strength", the cycle number times the X register, during the cycles
+/// `start`, `start + interval`, and so on, up to `stop`.
+fn signal_strength(input: &str, start: i64, interval: i64, stop: i64) -> i64 {
+    let cpu = Cpu::new(parse_program(input).unwrap());
+    cpu.take_while(|&(cycle, _)| cycle <= stop)
+        .filter(|&(cycle, _)| cycle >= start && (cycle - start) % interval == 0)
+        .map(|(cycle, register)| cycle * register)
+        .sum()
 }
 
-fn signal_strength(input: &str, start: i64, interval: i64, stop: i64) -> i64 {
-    let mut elapsed: i64 = 0;
-    let mut next_check: i64 = start;
-    let mut register: i64 = 1;
-    let mut signal: i64 = 0;
-    for line in input.split("\n") {
-        let inst: CPUInstruction = line.parse().unwrap();
-        let mut queued_change: i64 = 0;
-        match inst {
-            CPUInstruction::Noop => { elapsed += 1 },
-            CPUInstruction::AddX(val) => {
-                elapsed += 2;
-                queued_change = val;
-            }
-        }
 
-        if elapsed >= next_check {
-            signal += next_check * register;
-            next_check += interval;
-        }
-        register += queued_change;
-        if elapsed >= stop {
-            break;
-        }
-
-    }
-    signal
+/// Run the program and show what it draws on a CRT of the given size.
+fn draw_sprite(input: &str, width: usize, height: usize) -> Framebuffer {
+    Framebuffer::render(Cpu::new(parse_program(input).unwrap()), width, height)
 }
 
 
-fn draw_pixel(register: i64, elapsed: i64, row_size: i64) {
-    let xpos = elapsed % row_size;
-    if xpos == 0 {
-        println!();
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // `--program FILE` assembles the program in FILE and shows what it draws on the CRT, and
+    // `--disassemble FILE` shows the instructions it assembles to.
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--program" || arg == "--disassemble") {
+        let path = args.get(flag_idx + 1).ok_or("expected a filename")?;
+        let program = asm::assemble(&fs::read_to_string(path)?)?;
+        if args[flag_idx] == "--program" {
+            println!("{}", Framebuffer::render(Cpu::new(program), 40, 6));
+        } else {
+            println!("{}", asm::disassemble(&program));
+        }
+        return Ok(());
     }
-    if (register - xpos).abs() <= 1 {
-        print!("#");
-    } else {
-        print!(".");
+
+    // `--synthesize FILE` finds a short program that draws the picture in FILE
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--synthesize") {
+        let path = args.get(flag_idx + 1).ok_or("--synthesize needs a filename")?;
+        let target: Framebuffer = fs::read_to_string(path)?.parse()?;
+        println!("{}", asm::disassemble(&synth::synthesize(&target)?));
+        return Ok(());
     }
-}
 
+    // the other modes all run the puzzle input
+    let input = fs::read_to_string("input.txt")?;
 
-fn draw_sprite(input: &str, row_size: i64) {
-    let mut elapsed: i64 = 0;
-    let mut register: i64 = 1;
-    for line in input.split("\n") {
-        let inst: CPUInstruction = line.parse().unwrap();
-        match inst {
-            CPUInstruction::Noop => {
-                draw_pixel(register, elapsed, row_size);
-                elapsed += 1;
-            },
-            CPUInstruction::AddX(val) => {
-                draw_pixel(register, elapsed, row_size);
-                elapsed += 1;
-                draw_pixel(register, elapsed, row_size);
-                elapsed += 1;
-                register += val;
-            }
+    // `--debug` starts the step debugger on the input, and `--debug-script FILE` runs the
+    // debugger commands in FILE instead.
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--debug" || arg == "--debug-script") {
+        let cpu = Cpu::new(parse_program(&input)?);
+        let mut debugger = Debugger::new(cpu, 40, io::stdout());
+        if args[flag_idx] == "--debug-script" {
+            let script_path = args.get(flag_idx + 1).ok_or("--debug-script needs a filename")?;
+            debugger.run_script(&fs::read_to_string(script_path)?)?;
+        } else {
+            debugger.run_interactive(io::stdin().lock())?;
         }
+        return Ok(());
     }
-}
-
 
-fn main() {
-    let input = fs::read_to_string("input.txt").unwrap();
     println!("signal strength: {}", signal_strength(&input, 20, 40, 220));
 
-    draw_sprite(&input, 40);
+    // `--trace-csv FILE` and `--trace-vcd FILE` write what happens on every cycle to FILE. The
+    // trace gives up on programs that run longer than `--max-cycles N`.
+    let max_cycles = match args.iter().position(|arg| arg == "--max-cycles") {
+        Some(idx) => args.get(idx + 1).ok_or("--max-cycles needs a number")?.parse()?,
+        None => trace::DEFAULT_MAX_CYCLES,
+    };
+    for (idx, arg) in args.iter().enumerate() {
+        if arg == "--trace-csv" || arg == "--trace-vcd" {
+            let path = args.get(idx + 1).ok_or("expected a filename to write the trace to")?;
+            let rows = trace::trace(Cpu::new(parse_program(&input)?), 40, max_cycles)?;
+            let file = io::BufWriter::new(fs::File::create(path)?);
+            if arg == "--trace-csv" {
+                trace::write_csv(&rows, file)?;
+            } else {
+                trace::write_vcd(&rows, file)?;
+            }
+        }
+    }
+
+    let screen = draw_sprite(&input, 40, 6);
+    println!("{}", screen);
+    println!("letters on the screen: {}", screen.ocr()?);
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cpu::CPUInstruction;
 
     #[test]
     fn test_parse() {
@@ -250,6 +268,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::identity_op, clippy::neg_multiply)]
     fn test_small() {
         assert_eq!(
             signal_strength(SMALL_EXAMPLE, 1, 2, 5),
@@ -268,4 +287,17 @@ mod tests {
             13140
         )
     }
+
+    #[test]
+    fn test_draw_example() {
+        assert_eq!(
+            draw_sprite(TEST_INPUT, 40, 6).to_string(),
+            "##..##..##..##..##..##..##..##..##..##..
+###...###...###...###...###...###...###.
+####....####....####....####....####....
+#####.....#####.....#####.....#####.....
+######......######......######......####
+#######.......#######.......#######....."
+        );
+    }
 }
@@ -0,0 +1,186 @@
+//! Going backwards from a picture to a program: find the shortest program of `addx` and `noop`
+//! instructions that makes the CRT show a given image.
+//!
+//! The X register only changes at the end of an `addx`, which takes two cycles, so X has to
+//! stay the same for at least two pixels in a row before it can move. Some images can't be drawn
+//! at all; for example, X starts at 1, so the first pixel is lit unless the program is empty.
+use std::error::Error;
+use std::fmt;
+
+use crate::cpu::{CPUInstruction, Cpu};
+use crate::crt::Framebuffer;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SynthError {
+    /// No program can get past drawing this pixel correctly.
+    Impossible { row: usize, col: usize },
+    /// The program we came up with doesn't draw the image after all, which is a bug.
+    WrongImage { program: Vec<CPUInstruction> },
+}
+
+impl fmt::Display for SynthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SynthError::Impossible { row, col } => write!(
+                f,
+                "no program can draw this image; it goes wrong at row {}, column {}",
+                row, col
+            ),
+            SynthError::WrongImage { program } => write!(
+                f,
+                "the {}-instruction program we found doesn't draw the image",
+                program.len()
+            ),
+        }
+    }
+}
+
+impl Error for SynthError {}
+
+/// How we got to a state in the search: the previous state, and the instruction that got us
+/// from there to here.
+#[derive(Clone, Copy)]
+struct Step {
+    cost: usize,
+    prev: Option<(usize, usize, CPUInstruction)>,
+}
+
+/// Find a shortest program that draws exactly `target` when rendered with
+/// `Framebuffer::render`.
+///
+/// This is a shortest-path search over states (pixels drawn so far, value of X). Values of X
+/// that don't touch the screen are all equivalent, so we only need to consider X from -2 to
+/// width + 1. Since every instruction moves forward by one or two pixels, we can fill in the
+/// best way to reach each state in order of pixels drawn.
+pub fn synthesize(target: &Framebuffer) -> Result<Vec<CPUInstruction>, SynthError> {
+    let width = target.width();
+    let total = width * target.height();
+    let lowest_x: i64 = -2;
+    let num_x = width + 4;
+
+    let pixel_ok = |pixel: usize, x: i64| -> bool {
+        if pixel >= total {
+            // pixels after the end of the screen don't get drawn
+            return true;
+        }
+        let col = pixel % width;
+        let lit = (x - col as i64).abs() <= 1;
+        lit == target.get(col, pixel / width)
+    };
+
+    // dark_after[i] is true if every pixel from i onward is dark, so the program can just stop
+    let mut dark_after = vec![true; total + 1];
+    for pixel in (0..total).rev() {
+        dark_after[pixel] = dark_after[pixel + 1] && !target.get(pixel % width, pixel / width);
+    }
+
+    // best[i][x] is the shortest way to have drawn i pixels, with X = x + lowest_x
+    let mut best: Vec<Vec<Option<Step>>> = vec![vec![None; num_x]; total + 2];
+    best[0][(1 - lowest_x) as usize] = Some(Step { cost: 0, prev: None });
+    let mut finish: Option<(usize, usize)> = None;
+    let mut furthest: usize = 0;
+
+    for pixel in 0..=total + 1 {
+        for x_idx in 0..num_x {
+            let step = match best[pixel][x_idx] {
+                Some(step) => step,
+                None => continue,
+            };
+            furthest = pixel;
+            let done = pixel >= total || dark_after[pixel];
+            if done && finish.is_none_or(|(p, x)| best[p][x].unwrap().cost > step.cost) {
+                finish = Some((pixel, x_idx));
+            }
+            let x = x_idx as i64 + lowest_x;
+            if pixel >= total || !pixel_ok(pixel, x) {
+                continue;
+            }
+            let mut relax = |next: usize, next_x_idx: usize, inst: CPUInstruction| {
+                let candidate = Step { cost: step.cost + 1, prev: Some((pixel, x_idx, inst)) };
+                match best[next][next_x_idx] {
+                    Some(existing) if existing.cost <= candidate.cost => {}
+                    _ => best[next][next_x_idx] = Some(candidate),
+                }
+            };
+            relax(pixel + 1, x_idx, CPUInstruction::Noop);
+            if pixel_ok(pixel + 1, x) {
+                for next_x_idx in 0..num_x {
+                    let delta = next_x_idx as i64 - x_idx as i64;
+                    relax(pixel + 2, next_x_idx, CPUInstruction::AddX(delta));
+                }
+            }
+        }
+    }
+
+    let (mut pixel, mut x_idx) = finish.ok_or(SynthError::Impossible {
+        row: furthest / width,
+        col: furthest % width,
+    })?;
+    let mut program = Vec::new();
+    while let Some((prev_pixel, prev_x_idx, inst)) = best[pixel][x_idx].unwrap().prev {
+        program.push(inst);
+        pixel = prev_pixel;
+        x_idx = prev_x_idx;
+    }
+    program.reverse();
+    check(program, target)
+}
+
+/// Make sure a program really draws `target`, and pass it along if it does.
+fn check(program: Vec<CPUInstruction>, target: &Framebuffer) -> Result<Vec<CPUInstruction>, SynthError> {
+    let rendered = Framebuffer::render(Cpu::new(program.clone()), target.width(), target.height());
+    if &rendered != target {
+        return Err(SynthError::WrongImage { program });
+    }
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::parse_program;
+    use crate::TEST_INPUT;
+
+    #[test]
+    fn test_example_image() {
+        let target = Framebuffer::render(Cpu::new(parse_program(TEST_INPUT).unwrap()), 40, 6);
+        let program = synthesize(&target).unwrap();
+        assert_eq!(Framebuffer::render(Cpu::new(program.clone()), 40, 6), target);
+        assert!(program.len() <= parse_program(TEST_INPUT).unwrap().len());
+    }
+
+    #[test]
+    fn test_shortest() {
+        // all-dark after the first two pixels: move X off the screen and stop
+        let target: Framebuffer = "##........".parse().unwrap();
+        let program = synthesize(&target).unwrap();
+        assert_eq!(program.len(), 1);
+        assert_eq!(Framebuffer::render(Cpu::new(program), 10, 1), target);
+
+        // a sprite moving one column every two cycles
+        let target: Framebuffer = "##########".parse().unwrap();
+        assert_eq!(synthesize(&target).unwrap().len(), 5);
+
+        // the empty program draws nothing at all
+        let target: Framebuffer = "..........".parse().unwrap();
+        assert_eq!(synthesize(&target), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_impossible() {
+        let target: Framebuffer = ".#........".parse().unwrap();
+        assert_eq!(synthesize(&target), Err(SynthError::Impossible { row: 0, col: 0 }));
+        // X can't change after a single cycle
+        let target: Framebuffer = "#.#.#.#.#.".parse().unwrap();
+        assert_eq!(synthesize(&target), Err(SynthError::Impossible { row: 0, col: 1 }));
+    }
+
+    #[test]
+    fn test_check() {
+        let target = Framebuffer::render(Cpu::new(parse_program(TEST_INPUT).unwrap()), 40, 6);
+        let program = parse_program(TEST_INPUT).unwrap();
+        assert_eq!(check(program.clone(), &target), Ok(program));
+        let wrong = parse_program("noop\naddx 5").unwrap();
+        assert_eq!(check(wrong.clone(), &target), Err(SynthError::WrongImage { program: wrong }));
+    }
+}
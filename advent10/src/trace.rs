@@ -0,0 +1,187 @@
+//! Recording what the CPU does on every cycle, and writing it out as CSV, or as a Value Change
+//! Dump that a waveform viewer such as GTKWave can open.
+use std::io::{self, Write};
+
+use crate::cpu::{CPUInstruction, Cpu, CpuError};
+
+/// What happened during one cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRow {
+    pub cycle: i64,
+    /// The index of the instruction that was running.
+    pub pc: usize,
+    pub instruction: CPUInstruction,
+    /// The X register during the cycle.
+    pub x_before: i64,
+    /// The X register after the cycle, which is different if an instruction finished.
+    pub x_after: i64,
+    /// Whether the CRT lit the pixel it drew during this cycle.
+    pub lit: bool,
+}
+
+/// How many cycles to trace before deciding that a program isn't going to finish.
+pub const DEFAULT_MAX_CYCLES: i64 = 1_000_000;
+
+/// Run the program to the end, recording each cycle. `row_size` is the width of the CRT, which
+/// determines which pixel gets drawn. A program that overflows, or that's still running after
+/// `max_cycles` cycles, is an error.
+pub fn trace(mut cpu: Cpu, row_size: i64, max_cycles: i64) -> Result<Vec<TraceRow>, CpuError> {
+    let mut rows = Vec::new();
+    while let Some(instruction) = cpu.current_instruction() {
+        if cpu.elapsed() >= max_cycles {
+            return Err(CpuError::TooManyCycles(max_cycles));
+        }
+        let pc = cpu.pc();
+        let (cycle, x_before) = match cpu.try_step()? {
+            Some(during) => during,
+            None => break,
+        };
+        let col = (cycle - 1) % row_size;
+        rows.push(TraceRow {
+            cycle,
+            pc,
+            instruction,
+            x_before,
+            x_after: cpu.x(),
+            lit: (x_before - col).abs() <= 1,
+        });
+    }
+    Ok(rows)
+}
+
+pub fn write_csv(rows: &[TraceRow], mut out: impl Write) -> io::Result<()> {
+    writeln!(out, "cycle,pc,instruction,x_before,x_after,pixel")?;
+    for row in rows {
+        writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            row.cycle, row.pc, row.instruction, row.x_before, row.x_after, row.lit as u8
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the trace as a Value Change Dump, with one time unit per cycle. The signals are X, the
+/// program counter, and the pixel being drawn. Only changes are written, as the format expects.
+pub fn write_vcd(rows: &[TraceRow], mut out: impl Write) -> io::Result<()> {
+    writeln!(out, "$version advent10 $end")?;
+    writeln!(out, "$timescale 1s $end")?;
+    writeln!(out, "$scope module cpu $end")?;
+    writeln!(out, "$var integer 64 x x $end")?;
+    writeln!(out, "$var integer 64 p pc $end")?;
+    writeln!(out, "$var wire 1 l pixel $end")?;
+    writeln!(out, "$upscope $end")?;
+    writeln!(out, "$enddefinitions $end")?;
+
+    // integers are written in binary, and negative numbers in two's complement
+    let mut last: Option<(i64, usize, bool)> = None;
+    for row in rows {
+        let (x, pc, lit) = (row.x_before, row.pc, row.lit);
+        writeln!(out, "#{}", row.cycle)?;
+        if last.is_none_or(|(last_x, _, _)| last_x != x) {
+            writeln!(out, "b{:b} x", x as u64)?;
+        }
+        if last.is_none_or(|(_, last_pc, _)| last_pc != pc) {
+            writeln!(out, "b{:b} p", pc)?;
+        }
+        if last.is_none_or(|(_, _, last_lit)| last_lit != lit) {
+            writeln!(out, "{}l", lit as u8)?;
+        }
+        last = Some((x, pc, lit));
+    }
+    if let Some(row) = rows.last() {
+        writeln!(out, "#{}", row.cycle + 1)?;
+        if row.x_after != row.x_before {
+            writeln!(out, "b{:b} x", row.x_after as u64)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::parse_program;
+    use crate::{SMALL_EXAMPLE, SMALL_EXAMPLE_2};
+
+    #[test]
+    fn test_trace() {
+        let cpu = Cpu::new(parse_program(SMALL_EXAMPLE).unwrap());
+        let rows = trace(cpu, 40, DEFAULT_MAX_CYCLES).unwrap();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(
+            rows[3],
+            TraceRow {
+                cycle: 4,
+                pc: 2,
+                instruction: CPUInstruction::AddX(-5),
+                x_before: 4,
+                x_after: 4,
+                lit: true,
+            }
+        );
+        assert_eq!(rows[4].x_after, -1);
+        assert!(rows[4].lit);
+    }
+
+    #[test]
+    fn test_trace_errors() {
+        let cpu = Cpu::new(parse_program("noop\nset x 9223372036854775807\naddx 1").unwrap());
+        let overflow = CpuError::Overflow { pc: 2, inst: CPUInstruction::AddX(1) };
+        assert_eq!(trace(cpu, 40, DEFAULT_MAX_CYCLES), Err(overflow));
+        // this jumps back to itself forever
+        let cpu = Cpu::new(parse_program("noop\njmp 0").unwrap());
+        assert_eq!(trace(cpu, 40, 100), Err(CpuError::TooManyCycles(100)));
+        // but a program that finishes right at the limit is fine
+        let cpu = Cpu::new(parse_program(SMALL_EXAMPLE).unwrap());
+        assert_eq!(trace(cpu, 40, 5).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_csv() {
+        let cpu = Cpu::new(parse_program(SMALL_EXAMPLE).unwrap());
+        let rows = trace(cpu, 40, DEFAULT_MAX_CYCLES).unwrap();
+        let mut out = Vec::new();
+        write_csv(&rows, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "cycle,pc,instruction,x_before,x_after,pixel
+1,0,noop,1,1,1
+2,1,addx 3,1,1,1
+3,1,addx 3,1,4,1
+4,2,addx -5,4,4,1
+5,2,addx -5,4,-1,1
+"
+        );
+    }
+
+    #[test]
+    fn test_vcd() {
+        let cpu = Cpu::new(parse_program(SMALL_EXAMPLE_2).unwrap());
+        let rows = trace(cpu, 40, DEFAULT_MAX_CYCLES).unwrap();
+        let mut out = Vec::new();
+        write_vcd(&rows, &mut out).unwrap();
+        let vcd = String::from_utf8(out).unwrap();
+        let changes: Vec<&str> = vcd.split("$enddefinitions $end\n").collect();
+        assert_eq!(
+            changes[1],
+            "#1
+b1 x
+b0 p
+1l
+#2
+b1 p
+#3
+#4
+b100 x
+b10 p
+#5
+#6
+b1111111111111111111111111111111111111111111111111111111111111111 x
+b11 p
+0l
+#7
+"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! A small arbitrary-precision integer type, for following worry values exactly when they get
+//! too big for an i64.
+//!
+//! Numbers are stored as a sign and a magnitude, which is a list of 32-bit digits with the least
+//! significant first. Division truncates toward zero and remainders take the sign of the
+//! dividend, the same as Rust's integer types.
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BigInt {
+    negative: bool,
+    /// Never has trailing zero digits, so zero is an empty list.
+    magnitude: Vec<u32>,
+}
+
+fn trim(mut digits: Vec<u32>) -> Vec<u32> {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry: u64 = 0;
+    for idx in 0..a.len().max(b.len()) {
+        let sum = *a.get(idx).unwrap_or(&0) as u64 + *b.get(idx).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    result.push(carry as u32);
+    trim(result)
+}
+
+/// Subtract magnitudes, where `a` is at least as big as `b`.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow: i64 = 0;
+    for (idx, &digit) in a.iter().enumerate() {
+        let mut diff = digit as i64 - *b.get(idx).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        result.push(diff as u32);
+    }
+    trim(result)
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, &a_digit) in a.iter().enumerate() {
+        let mut carry: u64 = 0;
+        for (j, &b_digit) in b.iter().enumerate() {
+            let cur = result[i + j] as u64 + a_digit as u64 * b_digit as u64 + carry;
+            result[i + j] = cur as u32;
+            carry = cur >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    trim(result)
+}
+
+/// Long division of magnitudes, one bit at a time. `b` must not be zero.
+fn divmod_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for bit in (0..a.len() * 32).rev() {
+        // remainder = remainder * 2 + the next bit of a
+        let mut carry = (a[bit / 32] >> (bit % 32)) & 1;
+        for digit in remainder.iter_mut() {
+            let next_carry = *digit >> 31;
+            *digit = (*digit << 1) | carry;
+            carry = next_carry;
+        }
+        if carry != 0 {
+            remainder.push(carry);
+        }
+        if cmp_magnitude(&remainder, b) != Ordering::Less {
+            remainder = sub_magnitude(&remainder, b);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (trim(quotient), remainder)
+}
+
+/// Divide a magnitude by a single digit, which is much faster than the general case.
+fn divmod_small(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: u64 = 0;
+    for idx in (0..a.len()).rev() {
+        let cur = (remainder << 32) | a[idx] as u64;
+        quotient[idx] = (cur / divisor as u64) as u32;
+        remainder = cur % divisor as u64;
+    }
+    (trim(quotient), remainder as u32)
+}
+
+impl BigInt {
+    fn new(negative: bool, magnitude: Vec<u32>) -> Self {
+        let magnitude = trim(magnitude);
+        BigInt { negative: negative && !magnitude.is_empty(), magnitude }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    /// Divide, rounding toward zero, and also return the remainder. Returns None when dividing
+    /// by zero.
+    pub fn checked_divmod(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = if other.magnitude.len() == 1 {
+            let (quotient, remainder) = divmod_small(&self.magnitude, other.magnitude[0]);
+            (quotient, vec![remainder])
+        } else {
+            divmod_magnitude(&self.magnitude, &other.magnitude)
+        };
+        Some((
+            BigInt::new(self.negative != other.negative, quotient),
+            BigInt::new(self.negative, remainder),
+        ))
+    }
+
+    /// The remainder when dividing by a small positive number, which is how monkeys decide
+    /// where to throw things.
+    pub fn rem_u32(&self, divisor: u32) -> u32 {
+        divmod_small(&self.magnitude, divisor).1
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> Self {
+        let abs = n.unsigned_abs();
+        BigInt::new(n < 0, vec![abs as u32, (abs >> 32) as u32])
+    }
+}
+
+impl From<u64> for BigInt {
+    fn from(n: u64) -> Self {
+        BigInt::new(false, vec![n as u32, (n >> 32) as u32])
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitude(&self.magnitude, &other.magnitude));
+        }
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => {
+                BigInt::new(other.negative, sub_magnitude(&other.magnitude, &self.magnitude))
+            }
+            _ => BigInt::new(self.negative, sub_magnitude(&self.magnitude, &other.magnitude)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &(-other)
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::new(
+            self.negative != other.negative,
+            mul_magnitude(&self.magnitude, &other.magnitude),
+        )
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // split into groups of nine decimal digits, least significant first
+        const GROUP: u32 = 1_000_000_000;
+        let mut groups: Vec<u32> = Vec::new();
+        let mut rest = self.magnitude.clone();
+        while !rest.is_empty() {
+            let (quotient, remainder) = divmod_small(&rest, GROUP);
+            groups.push(remainder);
+            rest = quotient;
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", groups.last().unwrap())?;
+        for group in groups.iter().rev().skip(1) {
+            write!(f, "{:09}", group)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(n: i64) -> BigInt {
+        BigInt::from(n)
+    }
+
+    #[test]
+    fn test_small_arithmetic() {
+        let values = [0, 1, -1, 7, -13, 1 << 40, -(1 << 35) + 3, i64::MAX, i64::MIN + 1];
+        for &a in &values {
+            for &b in &values {
+                let (wide_a, wide_b) = (a as i128, b as i128);
+                assert_eq!((&big(a) + &big(b)).to_string(), (wide_a + wide_b).to_string());
+                assert_eq!((&big(a) - &big(b)).to_string(), (wide_a - wide_b).to_string());
+                assert_eq!((&big(a) * &big(b)).to_string(), (wide_a * wide_b).to_string());
+                if b != 0 {
+                    let (quotient, remainder) = big(a).checked_divmod(&big(b)).unwrap();
+                    assert_eq!(quotient.to_string(), (wide_a / wide_b).to_string());
+                    assert_eq!(remainder.to_string(), (wide_a % wide_b).to_string());
+                }
+            }
+        }
+        assert_eq!(big(5).checked_divmod(&big(0)), None);
+        assert_eq!(big(i64::MIN).to_string(), i64::MIN.to_string());
+    }
+
+    #[test]
+    fn test_big_numbers() {
+        // 2^100 = 1267650600228229401496703205376
+        let mut n = big(1);
+        for _ in 0..100 {
+            n = &n * &big(2);
+        }
+        assert_eq!(n.to_string(), "1267650600228229401496703205376");
+        assert_eq!(n.rem_u32(3), 1);
+        let square = &n * &n;
+        let (quotient, remainder) = square.checked_divmod(&n).unwrap();
+        assert_eq!(quotient, n);
+        assert!(remainder.is_zero());
+        let (quotient, remainder) = (&square + &big(12345)).checked_divmod(&(-&n)).unwrap();
+        assert_eq!(quotient, -&n);
+        assert_eq!(remainder, big(12345));
+        assert!((&n - &n).is_zero());
+    }
+}
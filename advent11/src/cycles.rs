@@ -0,0 +1,196 @@
+//! Fast-forwarding through a huge number of rounds.
+//!
+//! Each item moves between monkeys independently of the others, so we can follow one item at a
+//! time. At the start of each round, an item's state is which monkey holds it and its worry
+//! value. As long as the relief policy keeps worry values bounded, there are only finitely many
+//! states, and eventually the item returns to a state it's been in before. From then on, it goes
+//! around the same cycle forever, and we can count inspections for any number of rounds by
+//! multiplication. That's why this only takes a `BoundedRelief` policy: with any other policy,
+//! an item might never repeat a state.
+use std::collections::HashMap;
+
+use crate::relief::{BoundedRelief, ReliefPolicy, WorryError, WorryValue};
+use crate::Monkey;
+
+/// Follow one item through one round, starting from the monkey holding it. It's inspected by
+/// every monkey it's thrown to, until it gets thrown to a monkey whose turn has already passed.
+/// Adds the inspections to `counts`, and returns the item's state at the start of the next
+/// round.
+fn item_round<P: ReliefPolicy>(
+    monkeys: &[Monkey],
+    state: &(usize, P::Worry),
+    policy: &P,
+    counts: &mut [u64],
+) -> Result<(usize, P::Worry), WorryError> {
+    let mut holder = state.0;
+    let mut worry = state.1.clone();
+    loop {
+        counts[holder] += 1;
+        let (target, value) = monkeys[holder].inspect_and_throw_item(&worry, policy)?;
+        worry = value;
+        if target <= holder {
+            return Ok((target, worry));
+        }
+        holder = target;
+    }
+}
+
+/// Count how many items each monkey inspects over `num_rounds` rounds, without simulating every
+/// round when there are a lot of them.
+pub fn fast_forward_inspections<P: BoundedRelief>(
+    monkeys: &[Monkey],
+    num_rounds: u64,
+    policy: &P,
+) -> Result<Vec<u64>, WorryError> {
+    let num_monkeys = monkeys.len();
+    let mut totals: Vec<u64> = vec![0; num_monkeys];
+
+    // after the first round, every worry value has been through the relief policy, so there
+    // are only this many states it can be in. The starting worry value hasn't been, so it's one
+    // more state, and by the pigeonhole principle, an item has to repeat a state within that
+    // many rounds.
+    let num_states =
+        (num_monkeys as u64).saturating_mul(policy.num_worry_values()).saturating_add(1);
+
+    for monkey in monkeys {
+        for item in monkey.items.0.iter() {
+            // counts is how many times each monkey has inspected this item so far, and seen maps
+            // each state to the round it was first seen in
+            let mut counts: Vec<u64> = vec![0; num_monkeys];
+            let mut seen: HashMap<(usize, P::Worry), u64> = HashMap::new();
+            let first_state = (monkey.id, P::Worry::from_i64(item.worry));
+            let mut state = first_state.clone();
+            let mut round: u64 = 0;
+            let mut cycle_start: Option<u64> = None;
+            while round < num_rounds {
+                if let Some(&start) = seen.get(&state) {
+                    cycle_start = Some(start);
+                    break;
+                }
+                if seen.len() as u64 >= num_states {
+                    return Err(WorryError::NoCycle);
+                }
+                let next = item_round(monkeys, &state, policy, &mut counts)?;
+                seen.insert(state, round);
+                state = next;
+                round += 1;
+            }
+            drop(seen);
+
+            let item_counts: Vec<u64> = match cycle_start {
+                None => counts,
+                Some(start) => {
+                    // rounds `start` to `round` repeat forever. Besides the counts we have, we
+                    // need the counts after `start` rounds and after `leftover` more rounds; it's
+                    // cheaper to follow the item again than to remember the counts for every
+                    // round.
+                    let cycle_len = round - start;
+                    let num_cycles = (num_rounds - start) / cycle_len;
+                    let leftover = (num_rounds - start) % cycle_len;
+                    let mut partial: Vec<u64> = vec![0; num_monkeys];
+                    let mut state = first_state;
+                    for _ in 0..start {
+                        state = item_round(monkeys, &state, policy, &mut partial)?;
+                    }
+                    let before = partial.clone();
+                    for _ in 0..leftover {
+                        state = item_round(monkeys, &state, policy, &mut partial)?;
+                    }
+                    (0..num_monkeys)
+                        .map(|m| {
+                            num_cycles
+                                .checked_mul(counts[m] - before[m])
+                                .and_then(|repeated| repeated.checked_add(partial[m]))
+                                .ok_or(WorryError::Overflow)
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+            };
+            for (total, count) in totals.iter_mut().zip(item_counts) {
+                *total = total.checked_add(count).ok_or(WorryError::Overflow)?;
+            }
+        }
+    }
+    Ok(totals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relief::ModuloLcm;
+    use crate::{count_inspections, parse_monkey_list, TEST_INPUT};
+
+    fn check_matches<P: BoundedRelief>(monkeys: &[Monkey], policy: &P, rounds: &[usize]) {
+        for &num_rounds in rounds {
+            let fast = fast_forward_inspections(monkeys, num_rounds as u64, policy).unwrap();
+            let slow = count_inspections(monkeys, num_rounds, policy, None).unwrap();
+            let slow: Vec<u64> = slow.into_iter().map(|count| count as u64).collect();
+            assert_eq!(fast, slow, "{} rounds", num_rounds);
+        }
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let modulo = ModuloLcm::new(&monkeys).unwrap();
+        check_matches(&monkeys, &modulo, &[0, 1, 2, 5, 20, 100, 1000]);
+    }
+
+    #[test]
+    fn test_unreduced_start() {
+        // the starting worry value is bigger than anything the policy leaves behind, so the item
+        // never comes back to its first state
+        let input = "Monkey 0:
+  Starting items: 7
+  Operation: new = old + 1
+  Test: divisible by 2
+    If true: throw to monkey 0
+    If false: throw to monkey 0";
+        let monkeys = parse_monkey_list(input).unwrap();
+        let modulo = ModuloLcm::new(&monkeys).unwrap();
+        check_matches(&monkeys, &modulo, &[1, 2, 3, 10, 100]);
+        assert_eq!(fast_forward_inspections(&monkeys, 10, &modulo), Ok(vec![10]));
+    }
+
+    #[test]
+    fn test_huge_number_of_rounds() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let modulo = ModuloLcm::new(&monkeys).unwrap();
+        let counts = fast_forward_inspections(&monkeys, 1_000_000_000_000, &modulo).unwrap();
+        // there are 10 items, and each is inspected at least once a round
+        let total: u64 = counts.iter().sum();
+        assert!(total >= 10 * 1_000_000_000_000);
+    }
+
+    /// Says worry values are more bounded than they are.
+    struct Liar(ModuloLcm);
+
+    impl ReliefPolicy for Liar {
+        type Worry = i64;
+        fn relieve(&self, worry: i64) -> Result<i64, WorryError> {
+            self.0.relieve(worry)
+        }
+    }
+
+    impl BoundedRelief for Liar {
+        fn num_worry_values(&self) -> u64 {
+            1
+        }
+    }
+
+    #[test]
+    fn test_no_cycle() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let liar = Liar(ModuloLcm::new(&monkeys).unwrap());
+        // a few rounds are fine, since there's no need to look for a cycle
+        assert!(fast_forward_inspections(&monkeys, 3, &liar).is_ok());
+        assert_eq!(fast_forward_inspections(&monkeys, 1000, &liar), Err(WorryError::NoCycle));
+    }
+
+    #[test]
+    fn test_counts_overflow() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let modulo = ModuloLcm::new(&monkeys).unwrap();
+        assert_eq!(fast_forward_inspections(&monkeys, u64::MAX, &modulo), Err(WorryError::Overflow));
+    }
+}
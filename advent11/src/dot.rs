@@ -0,0 +1,102 @@
+//! Drawing the monkeys as a Graphviz graph, to see who throws to whom. Render it with something
+//! like `dot -Tsvg monkeys.dot -o monkeys.svg`.
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::Monkey;
+
+/// Escape a string for use inside a quoted DOT string.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Put some lines of text in double quotes, for a label. DOT writes line breaks as `\n`.
+fn quote(lines: &[&str]) -> String {
+    let lines: Vec<String> = lines.iter().map(|line| escape(line)).collect();
+    format!("\"{}\"", lines.join("\\n"))
+}
+
+/// Write the throw graph in DOT format. Each monkey is a node, labelled with its operation and
+/// divisor, with a green edge for where it throws when the test passes and a red one for when it
+/// fails. If `throws` is given, counted by (from, to) like `GameState::throws`, the edges are
+/// labelled with how many items went that way, and drawn thicker the more there were.
+pub fn to_dot(monkeys: &[Monkey], throws: Option<&HashMap<(usize, usize), u64>>) -> String {
+    let mut out = String::new();
+    let busiest = throws.and_then(|throws| throws.values().max().copied()).unwrap_or(0);
+
+    // writing to a String can't fail
+    writeln!(out, "digraph monkeys {{").unwrap();
+    writeln!(out, "    node [shape=box];").unwrap();
+    for monkey in monkeys {
+        let label = quote(&[
+            &format!("Monkey {}", monkey.id),
+            &monkey.op.to_string(),
+            &format!("divisible by {}", monkey.div),
+        ]);
+        writeln!(out, "    m{} [label={}];", monkey.id, label).unwrap();
+    }
+    for monkey in monkeys {
+        // a monkey that throws to the same place either way only gets one edge
+        let edges = if monkey.true_target == monkey.false_target {
+            vec![(monkey.true_target, "always", "black")]
+        } else {
+            vec![(monkey.true_target, "true", "darkgreen"), (monkey.false_target, "false", "red")]
+        };
+        for (target, label, color) in edges {
+            write!(out, "    m{} -> m{} [color={}", monkey.id, target, color).unwrap();
+            match throws {
+                Some(throws) => {
+                    let count = throws.get(&(monkey.id, target)).copied().unwrap_or(0);
+                    let width = 1.0 + 4.0 * count as f64 / busiest.max(1) as f64;
+                    let label = format!("{}: {}", label, count);
+                    write!(out, ", label={}, penwidth={:.1}", quote(&[&label]), width).unwrap();
+                }
+                None => write!(out, ", label={}", quote(&[label])).unwrap(),
+            }
+            writeln!(out, "];").unwrap();
+        }
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relief::DivideBy;
+    use crate::sim::GameState;
+    use crate::{parse_monkey_list, TEST_INPUT};
+
+    #[test]
+    fn test_structure() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let dot = to_dot(&monkeys, None);
+        assert!(dot.starts_with("digraph monkeys {\n"));
+        assert!(dot.contains("    m2 [label=\"Monkey 2\\nnew = old * old\\ndivisible by 13\"];\n"));
+        assert!(dot.contains("    m1 -> m2 [color=darkgreen, label=\"true\"];\n"));
+        assert!(dot.contains("    m1 -> m0 [color=red, label=\"false\"];\n"));
+        assert_eq!(dot.matches("->").count(), 8);
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_weighted() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let mut state = GameState::new(&monkeys);
+        state.play_round(&monkeys, &DivideBy(3), None).unwrap();
+        let dot = to_dot(&monkeys, Some(&state.throws));
+        // monkey 3 threw all 5 of its items to monkey 1, the most of any edge
+        assert!(dot.contains("    m3 -> m1 [color=red, label=\"false: 5\", penwidth=5.0];\n"));
+        assert!(dot.contains("    m3 -> m0 [color=darkgreen, label=\"true: 0\", penwidth=1.0];\n"));
+        assert!(dot.contains("    m0 -> m3 [color=red, label=\"false: 2\", penwidth=2.6];\n"));
+    }
+
+    #[test]
+    fn test_same_target_and_quoting() {
+        assert_eq!(quote(&["a \"b\"", "\\c"]), "\"a \\\"b\\\"\\n\\\\c\"");
+        let input = TEST_INPUT.replace("If false: throw to monkey 3\n\nMonkey 1", "If false: throw to monkey 2\n\nMonkey 1");
+        let dot = to_dot(&parse_monkey_list(&input).unwrap(), None);
+        assert!(dot.contains("    m0 -> m2 [color=black, label=\"always\"];\n"));
+        assert_eq!(dot.matches("->").count(), 7);
+    }
+}
@@ -0,0 +1,265 @@
+//! Arithmetic expressions for the monkeys' operations, like `new = (old - 1) * old`.
+//!
+//! Expressions are made of `old`, non-negative integer literals, the operators `+ - * / %`
+//! with the usual precedence (all left-associative), and parentheses.
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::relief::{WorryError, WorryValue};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinOp {
+    fn symbol(&self) -> char {
+        match self {
+            BinOp::Add => '+',
+            BinOp::Sub => '-',
+            BinOp::Mul => '*',
+            BinOp::Div => '/',
+            BinOp::Rem => '%',
+        }
+    }
+
+    /// Operators with higher precedence bind more tightly.
+    fn precedence(&self) -> u8 {
+        match self {
+            BinOp::Add | BinOp::Sub => 1,
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 2,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Expr {
+    Old,
+    Num(i64),
+    BinOp(Box<Expr>, BinOp, Box<Expr>),
+}
+
+impl Expr {
+    /// Compute the value of the expression, given the old worry value.
+    pub fn eval<W: WorryValue>(&self, old: &W) -> Result<W, WorryError> {
+        match self {
+            Expr::Old => Ok(old.clone()),
+            Expr::Num(n) => Ok(W::from_i64(*n)),
+            Expr::BinOp(left, op, right) => left.eval(old)?.binop(*op, &right.eval(old)?),
+        }
+    }
+
+    /// An expression is modulo-safe if it only uses `+`, `-` and `*`. Then, for any modulus m,
+    /// evaluating it on `old % m` gives the same answer mod m as evaluating it on `old`, which
+    /// is what lets us keep worry values small in part 2. Division and remainder don't work
+    /// that way.
+    pub fn is_modulo_safe(&self) -> bool {
+        match self {
+            Expr::Old | Expr::Num(_) => true,
+            Expr::BinOp(left, op, right) => {
+                !matches!(op, BinOp::Div | BinOp::Rem)
+                    && left.is_modulo_safe()
+                    && right.is_modulo_safe()
+            }
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::BinOp(_, op, _) => op.precedence(),
+            _ => u8::MAX,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    /// Write the expression with as few parentheses as possible, such that parsing it again
+    /// gives the same expression.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Old => write!(f, "old"),
+            Expr::Num(n) => write!(f, "{}", n),
+            Expr::BinOp(left, op, right) => {
+                if left.precedence() < op.precedence() {
+                    write!(f, "({})", left)?;
+                } else {
+                    write!(f, "{}", left)?;
+                }
+                write!(f, " {} ", op.symbol())?;
+                // everything is left-associative, so an operator on the right at the same
+                // precedence level needs parentheses
+                if right.precedence() <= op.precedence() {
+                    write!(f, "({})", right)
+                } else {
+                    write!(f, "{}", right)
+                }
+            }
+        }
+    }
+}
+
+/// A syntax error in an expression, at a byte offset into the expression.
+#[derive(PartialEq, Eq, Debug)]
+pub struct ExprError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "at offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl Error for ExprError {}
+
+/// A recursive-descent parser with one function for each level of precedence.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, ExprError> {
+        Err(ExprError { offset: self.pos, message: message.to_owned() })
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.input[self.pos..].chars().next()
+    }
+
+    /// Parse a sequence of operands joined by operators from `ops`, each operand being parsed
+    /// by `operand`.
+    fn binary_level(
+        &mut self,
+        ops: &[BinOp],
+        operand: fn(&mut Self) -> Result<Expr, ExprError>,
+    ) -> Result<Expr, ExprError> {
+        let mut expr = operand(self)?;
+        while let Some(ch) = self.peek() {
+            match ops.iter().find(|op| op.symbol() == ch) {
+                Some(op) => {
+                    self.pos += 1;
+                    expr = Expr::BinOp(Box::new(expr), *op, Box::new(operand(self)?));
+                }
+                None => break,
+            }
+        }
+        Ok(expr)
+    }
+
+    fn sum(&mut self) -> Result<Expr, ExprError> {
+        self.binary_level(&[BinOp::Add, BinOp::Sub], Self::product)
+    }
+
+    fn product(&mut self) -> Result<Expr, ExprError> {
+        self.binary_level(&[BinOp::Mul, BinOp::Div, BinOp::Rem], Self::atom)
+    }
+
+    fn atom(&mut self) -> Result<Expr, ExprError> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.sum()?;
+                if self.peek() != Some(')') {
+                    return self.error("expected ')'");
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(ch) if ch.is_ascii_digit() => {
+                let rest = &self.input[self.pos..];
+                let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                match rest[..len].parse() {
+                    Ok(n) => {
+                        self.pos += len;
+                        Ok(Expr::Num(n))
+                    }
+                    Err(_) => self.error("number is too big"),
+                }
+            }
+            Some(_) if self.input[self.pos..].starts_with("old") => {
+                self.pos += 3;
+                Ok(Expr::Old)
+            }
+            Some(_) => self.error("expected 'old', a number, or '('"),
+            None => self.error("unexpected end of expression"),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let expr = parser.sum()?;
+        if parser.peek().is_some() {
+            return parser.error("unexpected input after the expression");
+        }
+        Ok(expr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_eval() {
+        let expr: Expr = "old + old".parse().unwrap();
+        assert_eq!(expr, Expr::BinOp(Box::new(Expr::Old), BinOp::Add, Box::new(Expr::Old)));
+        assert_eq!(expr.eval(&7_i64).unwrap(), 14);
+        assert_eq!("old * 3 + 2".parse::<Expr>().unwrap().eval(&5_i64).unwrap(), 17);
+        assert_eq!("(old - 1) * old".parse::<Expr>().unwrap().eval(&5_i64).unwrap(), 20);
+        assert_eq!("old - 1 - 1".parse::<Expr>().unwrap().eval(&5_i64).unwrap(), 3);
+        assert_eq!("100 / old % 7".parse::<Expr>().unwrap().eval(&5_i64).unwrap(), 6);
+        assert_eq!("2*(3+(old))".parse::<Expr>().unwrap().eval(&1_i64).unwrap(), 8);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for text in [
+            "old * old",
+            "old * 19",
+            "old * 3 + 2",
+            "(old - 1) * old",
+            "old - (old - 1)",
+            "old / (2 * old) % 5",
+            "(old + 1) * (old + 2)",
+        ] {
+            let expr: Expr = text.parse().unwrap();
+            assert_eq!(expr.to_string(), text);
+        }
+        assert_eq!("((old))".parse::<Expr>().unwrap().to_string(), "old");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            "old +".parse::<Expr>(),
+            Err(ExprError { offset: 5, message: "unexpected end of expression".to_owned() })
+        );
+        assert_eq!("(old".parse::<Expr>().unwrap_err().offset, 4);
+        assert_eq!("old old".parse::<Expr>().unwrap_err().offset, 4);
+        assert_eq!("new".parse::<Expr>().unwrap_err().offset, 0);
+    }
+
+    #[test]
+    fn test_modulo_safe() {
+        assert!("(old - 1) * old + 3".parse::<Expr>().unwrap().is_modulo_safe());
+        assert!(!"old / 2".parse::<Expr>().unwrap().is_modulo_safe());
+        assert!(!"old * (old % 5)".parse::<Expr>().unwrap().is_modulo_safe());
+    }
+}
@@ -0,0 +1,113 @@
+//! Following individual items through the game, to explain where they went and why.
+use std::collections::HashMap;
+
+/// One time an item got inspected.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Inspection<W = i64> {
+    /// Rounds are numbered from 1, like in the puzzle.
+    pub round: usize,
+    pub monkey: usize,
+    pub worry_before: W,
+    /// The worry value the item has when it's thrown, after any relief.
+    pub worry_after: W,
+    /// The monkey the item was thrown to.
+    pub target: usize,
+}
+
+/// The history of every item, as a list of inspections for each item id.
+#[derive(Debug)]
+pub struct Lineage<W = i64> {
+    histories: HashMap<usize, Vec<Inspection<W>>>,
+}
+
+impl<W> Default for Lineage<W> {
+    fn default() -> Self {
+        Lineage { histories: HashMap::new() }
+    }
+}
+
+impl<W> Lineage<W> {
+    pub fn record(&mut self, item: usize, inspection: Inspection<W>) {
+        self.histories.entry(item).or_default().push(inspection);
+    }
+
+    /// Everything that happened to an item, in order.
+    pub fn history(&self, item: usize) -> &[Inspection<W>] {
+        match self.histories.get(&item) {
+            Some(history) => history,
+            None => &[],
+        }
+    }
+
+    /// The monkeys that inspected an item, in the order they first did so.
+    pub fn monkeys_visited(&self, item: usize) -> Vec<usize> {
+        let mut visited: Vec<usize> = Vec::new();
+        for inspection in self.history(item) {
+            if !visited.contains(&inspection.monkey) {
+                visited.push(inspection.monkey);
+            }
+        }
+        visited
+    }
+
+    /// The item that was inspected the most times, and how many times that was. Ties go to the
+    /// item with the lowest id.
+    pub fn most_inspected_item(&self) -> Option<(usize, usize)> {
+        self.histories
+            .iter()
+            .map(|(&item, history)| (item, history.len()))
+            .max_by_key(|&(item, count)| (count, std::cmp::Reverse(item)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relief::DivideBy;
+    use crate::{count_inspections, parse_monkey_list, TEST_INPUT};
+
+    #[test]
+    fn test_example_lineage() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let mut lineage = Lineage::default();
+        count_inspections(&monkeys, 1, &DivideBy(3), Some(&mut lineage)).unwrap();
+
+        // Item 0 starts with monkey 0 with worry 79, which becomes 79 * 19 / 3 = 500 and goes to
+        // monkey 3, which makes it 503 / 3 = 167 and throws it to monkey 1.
+        assert_eq!(
+            lineage.history(0),
+            &[
+                Inspection { round: 1, monkey: 0, worry_before: 79, worry_after: 500, target: 3 },
+                Inspection { round: 1, monkey: 3, worry_before: 500, worry_after: 167, target: 1 },
+            ]
+        );
+        assert_eq!(lineage.monkeys_visited(0), vec![0, 3]);
+        assert_eq!(lineage.history(99), &[]);
+    }
+
+    #[test]
+    fn test_queries() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let mut lineage = Lineage::default();
+        let counts = count_inspections(&monkeys, 20, &DivideBy(3), Some(&mut lineage)).unwrap();
+        let total: usize = (0..10).map(|item| lineage.history(item).len()).sum();
+        assert_eq!(total as i64, counts.iter().sum::<i64>());
+
+        let (item, count) = lineage.most_inspected_item().unwrap();
+        assert_eq!(lineage.history(item).len(), count);
+        for other in 0..10 {
+            assert!(lineage.history(other).len() <= count);
+        }
+
+        // following item 0 (79 at monkey 0) by hand for 4 rounds: 79 -> 500 -> 167 in round 1,
+        // 167 -> 57 -> 1083 -> 362 in round 2, 362 -> 122 in round 3, and 122 -> 772 -> 258 in
+        // round 4
+        let mut lineage = Lineage::default();
+        count_inspections(&monkeys, 4, &DivideBy(3), Some(&mut lineage)).unwrap();
+        let monkeys_in_order: Vec<usize> =
+            lineage.history(0).iter().map(|inspection| inspection.monkey).collect();
+        assert_eq!(monkeys_in_order, vec![0, 3, 1, 2, 3, 1, 0, 3]);
+        assert_eq!(lineage.history(0).last().unwrap().worry_after, 258);
+        assert_eq!(lineage.monkeys_visited(0), vec![0, 3, 1, 2]);
+    }
+}
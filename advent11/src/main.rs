@@ -1,5 +1,5 @@
 // An example to build from each day
-use std::cell::RefCell;
+use std::env;
 use std::error::Error;
 use std::fs;
 use std::fmt;
@@ -7,6 +7,21 @@ use std::num::ParseIntError;
 use std::str::FromStr;
 use parse_display::{Display, FromStr};
 
+mod bigint;
+mod cycles;
+mod dot;
+mod expr;
+mod lineage;
+mod relief;
+mod report;
+mod sim;
+mod validate;
+use expr::{Expr, ExprError};
+use lineage::Lineage;
+use relief::{ArbitraryPrecision, DivideBy, ModuloLcm, NoRelief, ReliefPolicy, WorryError, WorryValue};
+use report::Report;
+use sim::GameState;
+
 const DEBUG: bool = false;
 
 pub const TEST_INPUT: &str = "Monkey 0:
@@ -55,50 +70,62 @@ struct Monkey {
     activity: i64
 }
 
-#[derive(Display, FromStr, PartialEq, Debug)]
-enum Operation {
-    #[display("new = old + {0}")]
-    Add(i64),
-    #[display("new = old * old")]
-    Square,
-    #[display("new = old * {0}")]
-    Mul(i64),
-}
-use Operation::*;
-
-impl Operation {
-    fn apply(&self, val: i64) -> i64 {
-        match self {
-            Add(n) => val + n,
-            Square => val * val,
-            Mul(n) => val * n
+/// The operation a monkey does to an item's worry value when it inspects it, written like
+/// `new = old * 19`.
+#[derive(PartialEq, Debug)]
+struct Operation(Expr);
+
+impl FromStr for Operation {
+    type Err = ExprError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("new = ") {
+            Some(expr) => Ok(Operation(expr.parse()?)),
+            None => Err(ExprError { offset: 0, message: "expected 'new = '".to_owned() }),
         }
     }
 }
 
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "new = {}", self.0)
+    }
+}
+
+/// An item that the monkeys are throwing around. Its id stays the same as it moves between
+/// monkeys, so we can follow what happens to it.
+#[derive(PartialEq, Debug, Clone)]
+struct Item<W = i64> {
+    id: usize,
+    worry: W,
+}
+
+/// The items a monkey starts out holding. When written out, only their worry values are shown;
+/// items get their ids from the order they appear in the whole list of monkeys.
 #[derive(PartialEq, Debug)]
-struct ItemList(RefCell<Vec<i64>>);
+struct ItemList(Vec<Item>);
 
 impl FromStr for ItemList {
     type Err = ParseIntError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut nums: Vec<i64> = Vec::new();
-        for num_str in s.split(", ") {
-            nums.push(num_str.parse()?);
+        let mut items: Vec<Item> = Vec::new();
+        if s.is_empty() {
+            // a monkey can start out empty-handed
+            return Ok(ItemList(items));
+        }
+        for (id, num_str) in s.split(", ").enumerate() {
+            items.push(Item { id, worry: num_str.parse()? });
         }
-        let cell = RefCell::new(nums);
-        Ok(ItemList(cell))
+        Ok(ItemList(items))
     }
 }
 
 impl fmt::Display for ItemList {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let items: &Vec<i64> = &self.0.borrow();
-        for (idx, num) in items.iter().enumerate() {
+        for (idx, item) in self.0.iter().enumerate() {
             if idx > 0 {
                 write!(f, ", ")?;
             }
-            write!(f, "{}", num)?;
+            write!(f, "{}", item.worry)?;
         }
         Ok(())
     }
@@ -107,16 +134,17 @@ impl fmt::Display for ItemList {
 impl Monkey {
     /// Implement the thing where a monkey inspects an item and throws it to another monkey.
     /// Returns a tuple of the target monkey index, and the new "worry value" of the item.
-    fn inspect_and_throw_item(&self, worry: i64, chill_out: bool) -> (usize, i64) {
-        let mut value = worry;
+    fn inspect_and_throw_item<P: ReliefPolicy>(
+        &self,
+        worry: &P::Worry,
+        policy: &P,
+    ) -> Result<(usize, P::Worry), WorryError> {
         // println!("Monkey {} inspects an item with a worry level of {}.", self.id, worry);
-        value = self.op.apply(value);
+        let mut value = self.op.0.eval(worry)?;
         // println!("Value after operation is {}.", value);
-        if chill_out {
-            value /= 3;
-        }
-        // println!("Value is divided by 3 to give {}.", value);
-        let target = if value % self.div == 0 {
+        value = policy.relieve(value)?;
+        // println!("Value after relief is {}.", value);
+        let target = if value.is_divisible_by(self.div)? {
             // println!("Value is divisible by {}.", self.div);
             self.true_target
         } else {
@@ -124,55 +152,115 @@ impl Monkey {
             self.false_target
         };
         // println!("Item with worry level {} is thrown to monkey {}.\n", value, target);
-        (target, value)
+        Ok((target, value))
     }
 }
 
 fn parse_monkey_list(input: &str) -> Result<Vec<Monkey>, Box<dyn Error>> {
-    let mut monkeys = Vec::new();
+    let mut monkeys: Vec<Monkey> = Vec::new();
     for chunk in input.split("\n\n") {
         monkeys.push(chunk.parse()?);
     }
+    let mut next_id: usize = 0;
+    for monkey in monkeys.iter_mut() {
+        for item in monkey.items.0.iter_mut() {
+            item.id = next_id;
+            next_id += 1;
+        }
+    }
+    validate::validate(&monkeys)?;
     Ok(monkeys)
 }
 
-fn run_monkey_game(input: &str, num_rounds: usize, chill_out: bool) -> Result<i64, Box<dyn Error>> {
-    let monkeys: Vec<Monkey> = parse_monkey_list(input)?;
-
-    let modulo: i64 = monkeys.iter().map(|monkey| monkey.div).product();
-    dbg!(modulo);
-    let mut monkey_business: Vec<i64> = vec![0; monkeys.len()];
+/// Play the game for a number of rounds, and count how many items each monkey inspects. If
+/// `lineage` is given, every inspection gets recorded in it.
+fn count_inspections<P: ReliefPolicy>(
+    monkeys: &[Monkey],
+    num_rounds: usize,
+    policy: &P,
+    mut lineage: Option<&mut Lineage<P::Worry>>,
+) -> Result<Vec<i64>, WorryError> {
+    let mut state = GameState::new(monkeys);
     for _ in 0..num_rounds {
         if DEBUG {
-            for monkey in &monkeys {
-                println!("Monkey {}: {}", monkey.id, monkey.items);
-            }
-            println!();
-        }
-
-        for monkey in &monkeys {
-            let items: &mut Vec<i64> = &mut monkey.items.0.borrow_mut();
-            for item in items.iter() {
-                let (target, value) = monkey.inspect_and_throw_item(*item, chill_out);
-                monkey_business[monkey.id] += 1;
-                let target_item_list: &mut Vec<i64> = &mut monkeys[target].items.0.borrow_mut();
-                target_item_list.push(value % modulo);
-            }
-            items.drain(..);
+            println!("{}\n", state.describe());
         }
+        state.play_round(monkeys, policy, lineage.as_deref_mut())?;
     }
-    monkey_business.sort();
-    monkey_business.reverse();
-    dbg!(monkey_business.clone());
-    Ok(monkey_business[0] * monkey_business[1])
+    Ok(state.inspections)
+}
+
+/// Play the game for a number of rounds, and report how busy the monkeys were.
+fn play_monkey_game(input: &str, num_rounds: usize, chill_out: bool) -> Result<Report, Box<dyn Error>> {
+    let monkeys: Vec<Monkey> = parse_monkey_list(input)?;
+    // In part 1 we're relieved that items aren't damaged, and worry goes down. In part 2 we
+    // aren't, but the worry values can be kept modulo the monkeys' divisors.
+    let inspections = if chill_out {
+        count_inspections(&monkeys, num_rounds, &DivideBy(3), None)?
+    } else {
+        count_inspections(&monkeys, num_rounds, &ModuloLcm::new(&monkeys)?, None)?
+    };
+    let inspections = inspections.into_iter().map(|count| count as u64).collect();
+    Ok(Report::new(num_rounds as u64, inspections))
+}
+
+/// Like play_monkey_game, but follows each item separately and skips ahead once its path
+/// repeats, so it works for any number of rounds.
+fn play_monkey_game_fast(input: &str, num_rounds: u64) -> Result<Report, Box<dyn Error>> {
+    let monkeys: Vec<Monkey> = parse_monkey_list(input)?;
+    let policy = ModuloLcm::new(&monkeys)?;
+    let inspections = cycles::fast_forward_inspections(&monkeys, num_rounds, &policy)?;
+    Ok(Report::new(num_rounds, inspections))
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let input = fs::read_to_string("input.txt").unwrap();
-    let monkey_business = run_monkey_game(&input, 20, true)?;
-    println!("monkey business after 20 rounds: {}", monkey_business);
-    let monkey_business_2 = run_monkey_game(&input, 10000, false)?;
-    println!("monkey business after 10000 non-chill rounds: {}", monkey_business_2);
+
+    // `--dot FILE` writes the monkeys' throw graph to FILE, and `--dot-weighted FILE` also shows
+    // how many items went along each edge in part 1's 20 rounds.
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--dot" || arg == "--dot-weighted") {
+        let path = args.get(flag_idx + 1).ok_or("expected a filename to write the graph to")?;
+        let monkeys = parse_monkey_list(&input)?;
+        let graph = if args[flag_idx] == "--dot-weighted" {
+            let mut state = GameState::new(&monkeys);
+            for _ in 0..20 {
+                state.play_round(&monkeys, &DivideBy(3), None)?;
+            }
+            dot::to_dot(&monkeys, Some(&state.throws))
+        } else {
+            dot::to_dot(&monkeys, None)
+        };
+        fs::write(path, graph)?;
+        return Ok(());
+    }
+
+    println!("{}\n", play_monkey_game(&input, 20, true)?);
+
+    let mut lineage = Lineage::default();
+    count_inspections(&parse_monkey_list(&input)?, 20, &DivideBy(3), Some(&mut lineage))?;
+    if let Some((item, count)) = lineage.most_inspected_item() {
+        println!(
+            "item {} was inspected most, {} times, by monkeys {:?}",
+            item,
+            count,
+            lineage.monkeys_visited(item)
+        );
+    }
+
+    let monkeys = parse_monkey_list(&input)?;
+    println!("\nwithout relief, keeping worry values modulo {}", ModuloLcm::new(&monkeys)?.modulus());
+    println!("{}\n", play_monkey_game(&input, 10000, false)?);
+
+    // What if we just let the worry values grow? They get too big for an i64 quickly, but we
+    // can follow them exactly for a while.
+    if let Err(err) = count_inspections(&monkeys, 20, &NoRelief, None) {
+        println!("with no relief at all, in 20 rounds: {}", err);
+    }
+    let exact = count_inspections(&monkeys, 10, &ArbitraryPrecision, None)?;
+    println!("inspections after 10 rounds with exact worry values: {:?}", exact);
+
+    println!("\n{}", play_monkey_game_fast(&input, 1_000_000_000_000)?.with_top_k(3));
     Ok(())
 }
 
@@ -182,15 +270,80 @@ mod tests {
 
     #[test]
     fn test_example() -> Result<(), Box<dyn Error>> {
-        let monkey_business = run_monkey_game(TEST_INPUT, 20, true)?;
+        let monkey_business = play_monkey_game(TEST_INPUT, 20, true)?.monkey_business(2)?;
         assert_eq!(monkey_business, 10605);
         Ok(())
     }
 
     #[test]
     fn test_example_2() -> Result<(), Box<dyn Error>> {
-        let monkey_business = run_monkey_game(TEST_INPUT, 10000, false)?;
+        let monkey_business = play_monkey_game(TEST_INPUT, 10000, false)?.monkey_business(2)?;
         assert_eq!(monkey_business, 2713310158);
         Ok(())
     }
+
+    #[test]
+    fn test_fast_forward() -> Result<(), Box<dyn Error>> {
+        assert_eq!(play_monkey_game_fast(TEST_INPUT, 10000)?.monkey_business(2), Ok(2713310158));
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let text: Vec<String> = monkeys.iter().map(|monkey| monkey.to_string()).collect();
+        assert_eq!(text.join("\n\n"), TEST_INPUT);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let input = TEST_INPUT.replace("throw to monkey 3", "throw to monkey 5");
+        let err = parse_monkey_list(&input).unwrap_err();
+        assert_eq!(err.to_string(), "monkey 0 throws to monkey 5, which doesn't exist");
+    }
+
+    #[test]
+    fn test_item_ids() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let ids: Vec<usize> = monkeys[1].items.0.iter().map(|item| item.id).collect();
+        assert_eq!(ids, vec![2, 3, 4, 5]);
+        assert_eq!(monkeys[1].items.to_string(), "54, 65, 75, 74");
+    }
+
+    #[test]
+    fn test_parse_operations() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        assert_eq!(monkeys[2].op.to_string(), "new = old * old");
+        assert_eq!(monkeys[2].op.0.eval(&7_i64), Ok(49));
+
+        let monkey: Monkey = "Monkey 0:
+  Starting items: 1, 2
+  Operation: new = (old - 1) * old
+  Test: divisible by 3
+    If true: throw to monkey 0
+    If false: throw to monkey 0"
+            .parse()
+            .unwrap();
+        assert_eq!(monkey.op.0.eval(&4_i64), Ok(12));
+        assert!("Monkey 0:
+  Starting items: 1
+  Operation: old * 2
+  Test: divisible by 3
+    If true: throw to monkey 0
+    If false: throw to monkey 0"
+            .parse::<Monkey>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_custom_operations() -> Result<(), Box<dyn Error>> {
+        // the same game as the example, with equivalent operations written differently
+        let input = TEST_INPUT
+            .replace("new = old * 19", "new = old * 20 - old")
+            .replace("new = old + 6", "new = (old + 3) + 3")
+            .replace("new = old + 3", "new = 3 + old");
+        assert_eq!(play_monkey_game(&input, 20, true)?.monkey_business(2), Ok(10605));
+        assert_eq!(play_monkey_game(&input, 10000, false)?.monkey_business(2), Ok(2713310158));
+        Ok(())
+    }
 }
@@ -0,0 +1,235 @@
+//! Keeping worry levels manageable.
+//!
+//! After a monkey inspects an item, a relief policy decides what happens to the worry value
+//! before the monkey's test. The puzzle's part 1 divides it by 3; part 2 doesn't, but we can
+//! keep the value modulo the LCM of all the monkeys' divisors without changing where anything
+//! gets thrown. Arithmetic is checked, so a value that gets too big is an error instead of
+//! silently wrapping around.
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+use crate::bigint::BigInt;
+use crate::expr::BinOp;
+use crate::Monkey;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum WorryError {
+    Overflow,
+    DivideByZero,
+    /// Reducing worry values with a modulus only works if the operations are modulo-safe; this
+    /// monkey's operation isn't.
+    NotModuloSafe(usize),
+    /// An item went through every state a bounded relief policy allows without repeating one,
+    /// so the policy isn't as bounded as it says.
+    NoCycle,
+}
+
+impl fmt::Display for WorryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorryError::Overflow => write!(f, "worry value overflowed"),
+            WorryError::DivideByZero => write!(f, "worry value divided by zero"),
+            WorryError::NotModuloSafe(id) => write!(
+                f,
+                "monkey {}'s operation isn't modulo-safe, so worry values can't be reduced",
+                id
+            ),
+            WorryError::NoCycle => write!(f, "an item's path through the monkeys never repeated"),
+        }
+    }
+}
+
+impl Error for WorryError {}
+
+/// A type that can hold worry values.
+pub trait WorryValue: Clone + Eq + Hash + fmt::Debug + fmt::Display {
+    fn from_i64(n: i64) -> Self;
+    fn binop(&self, op: BinOp, other: &Self) -> Result<Self, WorryError>;
+    fn is_divisible_by(&self, divisor: i64) -> Result<bool, WorryError>;
+}
+
+impl WorryValue for i64 {
+    fn from_i64(n: i64) -> Self {
+        n
+    }
+
+    fn binop(&self, op: BinOp, other: &Self) -> Result<Self, WorryError> {
+        if matches!(op, BinOp::Div | BinOp::Rem) && *other == 0 {
+            return Err(WorryError::DivideByZero);
+        }
+        let result = match op {
+            BinOp::Add => self.checked_add(*other),
+            BinOp::Sub => self.checked_sub(*other),
+            BinOp::Mul => self.checked_mul(*other),
+            BinOp::Div => self.checked_div(*other),
+            BinOp::Rem => self.checked_rem(*other),
+        };
+        result.ok_or(WorryError::Overflow)
+    }
+
+    fn is_divisible_by(&self, divisor: i64) -> Result<bool, WorryError> {
+        Ok(self.binop(BinOp::Rem, &divisor)? == 0)
+    }
+}
+
+impl WorryValue for BigInt {
+    fn from_i64(n: i64) -> Self {
+        BigInt::from(n)
+    }
+
+    fn binop(&self, op: BinOp, other: &Self) -> Result<Self, WorryError> {
+        match op {
+            BinOp::Add => Ok(self + other),
+            BinOp::Sub => Ok(self - other),
+            BinOp::Mul => Ok(self * other),
+            BinOp::Div => Ok(self.checked_divmod(other).ok_or(WorryError::DivideByZero)?.0),
+            BinOp::Rem => Ok(self.checked_divmod(other).ok_or(WorryError::DivideByZero)?.1),
+        }
+    }
+
+    fn is_divisible_by(&self, divisor: i64) -> Result<bool, WorryError> {
+        match u32::try_from(divisor.unsigned_abs()) {
+            Ok(0) => Err(WorryError::DivideByZero),
+            Ok(small) => Ok(self.rem_u32(small) == 0),
+            Err(_) => Ok(self.binop(BinOp::Rem, &BigInt::from(divisor))?.is_zero()),
+        }
+    }
+}
+
+/// What happens to a worry value after a monkey's operation, and before its test.
+pub trait ReliefPolicy {
+    type Worry: WorryValue;
+    fn relieve(&self, worry: Self::Worry) -> Result<Self::Worry, WorryError>;
+}
+
+/// Divide the worry value by k, like in part 1 where k is 3. This rounds toward zero, which is
+/// rounding down for the positive values the puzzle has.
+pub struct DivideBy(pub i64);
+
+impl ReliefPolicy for DivideBy {
+    type Worry = i64;
+    fn relieve(&self, worry: i64) -> Result<i64, WorryError> {
+        worry.binop(BinOp::Div, &self.0)
+    }
+}
+
+/// A relief policy that only ever leaves a limited number of different worry values, which is
+/// what makes an item's path through the monkeys eventually repeat.
+pub trait BoundedRelief: ReliefPolicy {
+    /// How many different worry values there can be after relief.
+    fn num_worry_values(&self) -> u64;
+}
+
+/// Keep worry values modulo the least common multiple of the monkeys' divisors. Every monkey's
+/// test gives the same answer as it would on the real value, as long as every operation is
+/// modulo-safe.
+pub struct ModuloLcm(i64);
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl ModuloLcm {
+    pub fn new(monkeys: &[Monkey]) -> Result<Self, WorryError> {
+        let mut lcm: i64 = 1;
+        for monkey in monkeys {
+            if !monkey.op.0.is_modulo_safe() {
+                return Err(WorryError::NotModuloSafe(monkey.id));
+            }
+            if monkey.div == 0 {
+                return Err(WorryError::DivideByZero);
+            }
+            lcm = (lcm / gcd(lcm, monkey.div)).binop(BinOp::Mul, &monkey.div.abs())?;
+        }
+        Ok(ModuloLcm(lcm))
+    }
+
+    pub fn modulus(&self) -> i64 {
+        self.0
+    }
+}
+
+impl ReliefPolicy for ModuloLcm {
+    type Worry = i64;
+    fn relieve(&self, worry: i64) -> Result<i64, WorryError> {
+        Ok(worry.rem_euclid(self.0))
+    }
+}
+
+impl BoundedRelief for ModuloLcm {
+    fn num_worry_values(&self) -> u64 {
+        self.0.unsigned_abs()
+    }
+}
+
+/// No relief at all. With i64 values, this soon overflows.
+pub struct NoRelief;
+
+impl ReliefPolicy for NoRelief {
+    type Worry = i64;
+    fn relieve(&self, worry: i64) -> Result<i64, WorryError> {
+        Ok(worry)
+    }
+}
+
+/// No relief, but worry values are kept exactly, however big they get.
+pub struct ArbitraryPrecision;
+
+impl ReliefPolicy for ArbitraryPrecision {
+    type Worry = BigInt;
+    fn relieve(&self, worry: BigInt) -> Result<BigInt, WorryError> {
+        Ok(worry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{count_inspections, parse_monkey_list, TEST_INPUT};
+
+    #[test]
+    fn test_lcm() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        assert_eq!(ModuloLcm::new(&monkeys).unwrap().modulus(), 23 * 19 * 13 * 17);
+
+        let shared = TEST_INPUT.replace("divisible by 23", "divisible by 26");
+        let monkeys = parse_monkey_list(&shared).unwrap();
+        assert_eq!(ModuloLcm::new(&monkeys).unwrap().modulus(), 26 * 19 * 17);
+
+        let unsafe_op = TEST_INPUT.replace("new = old + 6", "new = old / 2");
+        let monkeys = parse_monkey_list(&unsafe_op).unwrap();
+        assert_eq!(ModuloLcm::new(&monkeys).err(), Some(WorryError::NotModuloSafe(1)));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        assert_eq!(
+            count_inspections(&monkeys, 10000, &NoRelief, None),
+            Err(WorryError::Overflow)
+        );
+        assert_eq!(i64::MAX.binop(BinOp::Add, &1), Err(WorryError::Overflow));
+        assert_eq!(5.binop(BinOp::Rem, &0), Err(WorryError::DivideByZero));
+    }
+
+    #[test]
+    fn test_policies_agree() {
+        // exact values and values modulo the LCM send items to the same places
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        for num_rounds in 0..8 {
+            let exact = count_inspections(&monkeys, num_rounds, &ArbitraryPrecision, None);
+            let modulo = count_inspections(&monkeys, num_rounds, &ModuloLcm::new(&monkeys).unwrap(), None);
+            assert_eq!(exact, modulo);
+        }
+        // and in part 1, values are small enough that exact values don't matter
+        assert_eq!(
+            count_inspections(&monkeys, 20, &DivideBy(3), None),
+            Ok(vec![101, 95, 7, 105])
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! A summary of a game: how many items each monkey inspected, and who was busiest.
+use std::fmt;
+
+use crate::bigint::BigInt;
+use crate::relief::WorryError;
+
+pub struct Report {
+    num_rounds: u64,
+    /// How many items each monkey inspected, indexed by monkey id.
+    inspections: Vec<u64>,
+    /// How many of the busiest monkeys to show.
+    top_k: usize,
+}
+
+impl Report {
+    pub fn new(num_rounds: u64, inspections: Vec<u64>) -> Self {
+        Report { num_rounds, inspections, top_k: 2 }
+    }
+
+    /// Show the `k` busiest monkeys in the report, instead of two.
+    pub fn with_top_k(mut self, k: usize) -> Self {
+        self.top_k = k;
+        self
+    }
+
+    /// The `k` monkeys that inspected the most items, busiest first, as (id, inspections).
+    /// Ties go to the monkey with the lower id.
+    pub fn top(&self, k: usize) -> Vec<(usize, u64)> {
+        let mut ranked: Vec<(usize, u64)> = self.inspections.iter().copied().enumerate().collect();
+        ranked.sort_by(|(id_a, count_a), (id_b, count_b)| count_b.cmp(count_a).then(id_a.cmp(id_b)));
+        ranked.truncate(k);
+        ranked
+    }
+
+    /// The level of monkey business: the product of the inspection counts of the `k` busiest
+    /// monkeys. The puzzle uses two. Three counts near u64::MAX don't fit in a u128, so this
+    /// fails with an overflow rather than wrapping around.
+    pub fn monkey_business(&self, k: usize) -> Result<u128, WorryError> {
+        self.top(k)
+            .iter()
+            .try_fold(1u128, |product, &(_, count)| product.checked_mul(count as u128))
+            .ok_or(WorryError::Overflow)
+    }
+
+    /// The same as `monkey_business`, but it can't overflow.
+    fn exact_monkey_business(&self, k: usize) -> BigInt {
+        self.top(k)
+            .iter()
+            .fold(BigInt::from(1u64), |product, &(_, count)| &product * &BigInt::from(count))
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "after {} rounds:", self.num_rounds)?;
+        writeln!(f, "monkey  inspections")?;
+        for (id, count) in self.inspections.iter().enumerate() {
+            writeln!(f, "{:>6}  {:>11}", id, count)?;
+        }
+        let top: Vec<String> = self
+            .top(self.top_k)
+            .iter()
+            .map(|(id, count)| format!("monkey {} ({})", id, count))
+            .collect();
+        writeln!(f, "top {}: {}", self.top_k, top.join(", "))?;
+        match self.monkey_business(self.top_k) {
+            Ok(monkey_business) => write!(f, "monkey business: {}", monkey_business),
+            Err(_) => write!(f, "monkey business: {}", self.exact_monkey_business(self.top_k)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let report = Report::new(20, vec![101, 95, 7, 105]);
+        assert_eq!(report.top(2), vec![(3, 105), (0, 101)]);
+        assert_eq!(report.monkey_business(2), Ok(10605));
+        assert_eq!(report.monkey_business(3), Ok(10605 * 95));
+        assert_eq!(
+            report.to_string(),
+            "after 20 rounds:
+monkey  inspections
+     0          101
+     1           95
+     2            7
+     3          105
+top 2: monkey 3 (105), monkey 0 (101)
+monkey business: 10605"
+        );
+    }
+
+    #[test]
+    fn test_ties_and_small_games() {
+        let report = Report::new(1, vec![4, 9, 4]).with_top_k(5);
+        assert_eq!(report.top(5), vec![(1, 9), (0, 4), (2, 4)]);
+        assert!(report.to_string().ends_with("top 5: monkey 1 (9), monkey 0 (4), monkey 2 (4)
+monkey business: 144"));
+    }
+
+    #[test]
+    fn test_big_counts() {
+        let report =
+            Report::new(u64::MAX, vec![u64::MAX, u64::MAX - 1, u64::MAX, 3]).with_top_k(3);
+        assert_eq!(report.monkey_business(2), Ok(u64::MAX as u128 * u64::MAX as u128));
+        assert_eq!(report.monkey_business(3), Err(WorryError::Overflow));
+        assert_eq!(report.monkey_business(4), Err(WorryError::Overflow));
+        // the report still shows the whole product, (2^64 - 1)^2 * (2^64 - 2)
+        let exact = "6277101735386680762474659955523912562341090735105509425150";
+        assert!(report.to_string().ends_with(&format!("monkey business: {}", exact)));
+    }
+}
@@ -0,0 +1,150 @@
+//! The state of a game in progress. The monkeys' definitions don't change during the game, so
+//! they're kept separate from the items being thrown around, which live here.
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+
+use crate::lineage::{Inspection, Lineage};
+use crate::relief::{ReliefPolicy, WorryError, WorryValue};
+use crate::{Item, Monkey};
+
+pub struct GameState<W> {
+    /// The items each monkey is holding, in the order it will inspect them.
+    pub queues: Vec<VecDeque<Item<W>>>,
+    /// How many items each monkey has inspected so far.
+    pub inspections: Vec<i64>,
+    /// How many items have been thrown from one monkey to another, keyed by (from, to).
+    pub throws: HashMap<(usize, usize), u64>,
+    /// The number of rounds that have been played.
+    pub round: usize,
+}
+
+impl<W: WorryValue> GameState<W> {
+    /// Start a game, with each monkey holding its starting items.
+    pub fn new(monkeys: &[Monkey]) -> Self {
+        let starting_items = |monkey: &Monkey| -> VecDeque<Item<W>> {
+            monkey
+                .items
+                .0
+                .iter()
+                .map(|item| Item { id: item.id, worry: W::from_i64(item.worry) })
+                .collect()
+        };
+        GameState {
+            queues: monkeys.iter().map(starting_items).collect(),
+            inspections: vec![0; monkeys.len()],
+            throws: HashMap::new(),
+            round: 0,
+        }
+    }
+
+    /// Play one round, where each monkey in turn inspects and throws all the items it's holding.
+    ///
+    /// A monkey takes all of its items at the start of its turn. If it throws an item to
+    /// itself, the item goes to the back of its queue and waits until its next turn, just like
+    /// an item thrown to a monkey that already had its turn this round.
+    pub fn play_round<P: ReliefPolicy<Worry = W>>(
+        &mut self,
+        monkeys: &[Monkey],
+        policy: &P,
+        mut lineage: Option<&mut Lineage<W>>,
+    ) -> Result<(), WorryError> {
+        self.round += 1;
+        for monkey in monkeys {
+            let items = mem::take(&mut self.queues[monkey.id]);
+            for item in items {
+                let (target, worry) = monkey.inspect_and_throw_item(&item.worry, policy)?;
+                self.inspections[monkey.id] += 1;
+                *self.throws.entry((monkey.id, target)).or_insert(0) += 1;
+                if let Some(lineage) = lineage.as_deref_mut() {
+                    lineage.record(item.id, Inspection {
+                        round: self.round,
+                        monkey: monkey.id,
+                        worry_before: item.worry,
+                        worry_after: worry.clone(),
+                        target,
+                    });
+                }
+                self.queues[target].push_back(Item { id: item.id, worry });
+            }
+        }
+        Ok(())
+    }
+
+    /// The worry values of the items each monkey holds, like `Monkey 0: 20, 23, 27, 26`.
+    pub fn describe(&self) -> String {
+        let lines: Vec<String> = self
+            .queues
+            .iter()
+            .enumerate()
+            .map(|(id, queue)| {
+                let worries: Vec<String> = queue.iter().map(|item| item.worry.to_string()).collect();
+                format!("Monkey {}: {}", id, worries.join(", "))
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cycles::fast_forward_inspections;
+    use crate::relief::{DivideBy, ModuloLcm};
+    use crate::{parse_monkey_list, TEST_INPUT};
+
+    #[test]
+    fn test_first_round() {
+        let monkeys = parse_monkey_list(TEST_INPUT).unwrap();
+        let mut state = GameState::new(&monkeys);
+        state.play_round(&monkeys, &DivideBy(3), None).unwrap();
+        // from the puzzle text
+        assert_eq!(
+            state.describe(),
+            "Monkey 0: 20, 23, 27, 26
+Monkey 1: 2080, 25, 167, 207, 401, 1046
+Monkey 2: 
+Monkey 3: "
+        );
+        assert_eq!(state.inspections, vec![2, 4, 3, 5]);
+        let mut throws: Vec<((usize, usize), u64)> = state.throws.into_iter().collect();
+        throws.sort();
+        assert_eq!(throws, vec![((0, 3), 2), ((1, 0), 4), ((2, 1), 1), ((2, 3), 2), ((3, 1), 5)]);
+    }
+
+    #[test]
+    fn test_self_throw() {
+        let monkeys = parse_monkey_list(
+            "Monkey 0:
+  Starting items: 2
+  Operation: new = old + 1
+  Test: divisible by 2
+    If true: throw to monkey 0
+    If false: throw to monkey 1
+
+Monkey 1:
+  Starting items: 1
+  Operation: new = old * 2
+  Test: divisible by 5
+    If true: throw to monkey 0
+    If false: throw to monkey 1",
+        )
+        .unwrap();
+        let modulo = ModuloLcm::new(&monkeys).unwrap();
+        let mut state = GameState::new(&monkeys);
+
+        // monkey 0 throws 3 to monkey 1, which doubles it and its own item and keeps them
+        state.play_round(&monkeys, &modulo, None).unwrap();
+        assert_eq!(state.describe(), "Monkey 0: \nMonkey 1: 2, 6");
+        assert_eq!(state.inspections, vec![1, 2]);
+
+        // 2 and 6 become 4 and 12, which is 2 modulo 10
+        state.play_round(&monkeys, &modulo, None).unwrap();
+        assert_eq!(state.describe(), "Monkey 0: \nMonkey 1: 4, 2");
+        assert_eq!(state.inspections, vec![1, 4]);
+        let ids: Vec<usize> = state.queues[1].iter().map(|item| item.id).collect();
+        assert_eq!(ids, vec![1, 0]);
+
+        // following items one at a time agrees about self-throws
+        assert_eq!(fast_forward_inspections(&monkeys, 2, &modulo), Ok(vec![1, 4]));
+    }
+}
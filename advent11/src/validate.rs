@@ -0,0 +1,120 @@
+//! Checking that a list of monkeys makes sense before playing with them.
+//!
+//! The game looks monkeys up by id and by throw target, so ids have to be 0, 1, 2, ... in order,
+//! and every target has to be one of them. Divisors can't be zero, and every monkey should be
+//! able to get hold of an item at some point, or it's not really part of the game.
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+
+use crate::Monkey;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ConfigError {
+    /// The monkey at this position in the list has the wrong id.
+    WrongId { position: usize, id: usize },
+    UnknownTarget { monkey: usize, target: usize },
+    ZeroDivisor(usize),
+    /// No item can ever get to this monkey.
+    Unreachable(usize),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::WrongId { position, id } => {
+                write!(f, "monkey number {} in the list has id {}", position, id)
+            }
+            ConfigError::UnknownTarget { monkey, target } => {
+                write!(f, "monkey {} throws to monkey {}, which doesn't exist", monkey, target)
+            }
+            ConfigError::ZeroDivisor(monkey) => write!(f, "monkey {} tests divisibility by 0", monkey),
+            ConfigError::Unreachable(monkey) => write!(f, "monkey {} never gets any items", monkey),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Check the monkeys, returning the first problem found. Problems with ids come first, since
+/// the other checks depend on them.
+pub fn validate(monkeys: &[Monkey]) -> Result<(), ConfigError> {
+    for (position, monkey) in monkeys.iter().enumerate() {
+        if monkey.id != position {
+            return Err(ConfigError::WrongId { position, id: monkey.id });
+        }
+    }
+    for monkey in monkeys {
+        for target in [monkey.true_target, monkey.false_target] {
+            if target >= monkeys.len() {
+                return Err(ConfigError::UnknownTarget { monkey: monkey.id, target });
+            }
+        }
+        if monkey.div == 0 {
+            return Err(ConfigError::ZeroDivisor(monkey.id));
+        }
+    }
+
+    // search outward from the monkeys that start with items, along the throws
+    let mut reachable = vec![false; monkeys.len()];
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    for monkey in monkeys.iter().filter(|monkey| !monkey.items.0.is_empty()) {
+        reachable[monkey.id] = true;
+        queue.push_back(monkey.id);
+    }
+    while let Some(id) = queue.pop_front() {
+        for target in [monkeys[id].true_target, monkeys[id].false_target] {
+            if !reachable[target] {
+                reachable[target] = true;
+                queue.push_back(target);
+            }
+        }
+    }
+    match reachable.iter().position(|&seen| !seen) {
+        Some(id) => Err(ConfigError::Unreachable(id)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TEST_INPUT;
+
+    fn parse_unchecked(input: &str) -> Vec<Monkey> {
+        input.split("\n\n").map(|chunk| chunk.parse().unwrap()).collect()
+    }
+
+    fn check(input: &str) -> Result<(), ConfigError> {
+        validate(&parse_unchecked(input))
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(check(TEST_INPUT), Ok(()));
+        assert_eq!(
+            check(&TEST_INPUT.replace("Monkey 2:", "Monkey 7:")),
+            Err(ConfigError::WrongId { position: 2, id: 7 })
+        );
+        assert_eq!(
+            check(&TEST_INPUT.replace("If false: throw to monkey 0", "If false: throw to monkey 4")),
+            Err(ConfigError::UnknownTarget { monkey: 1, target: 4 })
+        );
+        assert_eq!(
+            check(&TEST_INPUT.replace("divisible by 13", "divisible by 0")),
+            Err(ConfigError::ZeroDivisor(2))
+        );
+    }
+
+    #[test]
+    fn test_unreachable() {
+        // monkey 2 starts with nothing, and nobody throws to it any more
+        let input = TEST_INPUT
+            .replace("Starting items: 79, 60, 97", "Starting items: ")
+            .replace("If true: throw to monkey 2", "If true: throw to monkey 3");
+        assert_eq!(check(&input), Err(ConfigError::Unreachable(2)));
+        // but it's fine for a monkey to start with nothing if it gets thrown things
+        let input = TEST_INPUT.replace("Starting items: 79, 60, 97", "Starting items: ");
+        assert_eq!(check(&input), Ok(()));
+    }
+}
@@ -0,0 +1,229 @@
+//! Comparing packets, step by step.
+//!
+//! The comparison is written once, and reports each step it takes to an observer.
+//! `packet_cmp`, which the `Ord` impl for `Value` is built on, uses an observer that ignores
+//! everything, which compiles down to a plain comparison, and `explain_cmp` uses one that
+//! writes the steps out the way the puzzle does. Either way it's the same code deciding the
+//! answer, so the explanation can't disagree with the real comparison.
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Write};
+use std::slice;
+
+use crate::Value;
+
+/// Something that wants to know how a comparison went. `depth` is how deeply nested the step
+/// is.
+pub trait Observer {
+    fn compare(&mut self, depth: usize, left: &dyn Display, right: &dyn Display);
+    fn note(&mut self, depth: usize, message: fmt::Arguments);
+}
+
+/// An observer that doesn't care.
+pub struct Silent;
+
+impl Observer for Silent {
+    fn compare(&mut self, _depth: usize, _left: &dyn Display, _right: &dyn Display) {}
+    fn note(&mut self, _depth: usize, _message: fmt::Arguments) {}
+}
+
+/// Writes the steps as an indented list.
+struct Trace(String);
+
+impl Observer for Trace {
+    fn compare(&mut self, depth: usize, left: &dyn Display, right: &dyn Display) {
+        // writing to a String can't fail
+        writeln!(self.0, "{:indent$}- Compare {} vs {}", "", left, right, indent = depth * 2).unwrap();
+    }
+
+    fn note(&mut self, depth: usize, message: fmt::Arguments) {
+        writeln!(self.0, "{:indent$}- {}", "", message, indent = depth * 2).unwrap();
+    }
+}
+
+/// A list that's borrowed from somewhere, such as an int that's being treated as a list.
+struct List<'a>(&'a [Value]);
+
+impl Display for List<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+        for (idx, item) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", item)?;
+        }
+        write!(f, "]")
+    }
+}
+
+pub fn compare_values(left: &Value, right: &Value, depth: usize, observer: &mut impl Observer) -> Ordering {
+    observer.compare(depth, left, right);
+    match (left, right) {
+        (Value::Int(left_int), Value::Int(right_int)) => {
+            let ordering = left_int.cmp(right_int);
+            match ordering {
+                Ordering::Less => {
+                    observer.note(depth + 1, format_args!("Left side is smaller, so inputs are in the right order"))
+                }
+                Ordering::Greater => observer.note(
+                    depth + 1,
+                    format_args!("Right side is smaller, so inputs are not in the right order"),
+                ),
+                Ordering::Equal => {}
+            }
+            ordering
+        }
+        (Value::Array(left_items), Value::Array(right_items)) => {
+            compare_lists(left_items, right_items, depth, observer)
+        }
+        // an int gets compared to a list as if it were a list of one int. That list is a slice
+        // borrowing the int, so nothing gets copied.
+        (Value::Int(n), Value::Array(right_items)) => {
+            let left_items = slice::from_ref(left);
+            observer.note(depth + 1, format_args!("Mixed types; convert left to [{}] and retry comparison", n));
+            observer.compare(depth + 1, &List(left_items), right);
+            compare_lists(left_items, right_items, depth + 1, observer)
+        }
+        (Value::Array(left_items), Value::Int(n)) => {
+            let right_items = slice::from_ref(right);
+            observer.note(depth + 1, format_args!("Mixed types; convert right to [{}] and retry comparison", n));
+            observer.compare(depth + 1, left, &List(right_items));
+            compare_lists(left_items, right_items, depth + 1, observer)
+        }
+    }
+}
+
+/// Compare two lists item by item, where the lists themselves were compared at `depth`.
+fn compare_lists(left: &[Value], right: &[Value], depth: usize, observer: &mut impl Observer) -> Ordering {
+    for (left_item, right_item) in left.iter().zip(right) {
+        match compare_values(left_item, right_item, depth + 1, observer) {
+            Ordering::Equal => continue,
+            unequal => return unequal,
+        }
+    }
+    let ordering = left.len().cmp(&right.len());
+    match ordering {
+        Ordering::Less => observer.note(
+            depth + 1,
+            format_args!("Left side ran out of items, so inputs are in the right order"),
+        ),
+        Ordering::Greater => observer.note(
+            depth + 1,
+            format_args!("Right side ran out of items, so inputs are not in the right order"),
+        ),
+        Ordering::Equal => {}
+    }
+    ordering
+}
+
+/// Compare packets the way the puzzle says to. This isn't quite a total order, because an int
+/// and a list of just that int come out equal, like `2` and `[2]`; `Ord for Value` breaks those
+/// ties with `structural_cmp`.
+pub fn packet_cmp(left: &Value, right: &Value) -> Ordering {
+    compare_values(left, right, 0, &mut Silent)
+}
+
+/// Compare values by their structure, with ints before lists. This is only used to break ties
+/// between values that the puzzle's comparison considers equal, so it just needs to be a total
+/// order that's consistent with `==`.
+pub fn structural_cmp(left: &Value, right: &Value) -> Ordering {
+    match (left, right) {
+        (Value::Int(left_int), Value::Int(right_int)) => left_int.cmp(right_int),
+        (Value::Int(_), Value::Array(_)) => Ordering::Less,
+        (Value::Array(_), Value::Int(_)) => Ordering::Greater,
+        (Value::Array(left_items), Value::Array(right_items)) => {
+            for (left_item, right_item) in left_items.iter().zip(right_items) {
+                match structural_cmp(left_item, right_item) {
+                    Ordering::Equal => continue,
+                    unequal => return unequal,
+                }
+            }
+            left_items.len().cmp(&right_items.len())
+        }
+    }
+}
+
+/// Explain how two packets compare, with one line for each step of the comparison.
+pub fn explain_cmp(left: &Value, right: &Value) -> String {
+    let mut trace = Trace(String::new());
+    compare_values(left, right, 0, &mut trace);
+    trace.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_pairs;
+    use crate::TEST_INPUT;
+
+    fn explain_pair(idx: usize) -> String {
+        let (left, right) = &parse_pairs(TEST_INPUT).unwrap()[idx];
+        explain_cmp(left, right)
+    }
+
+    #[test]
+    fn test_puzzle_traces() {
+        // from the puzzle text
+        assert_eq!(
+            explain_pair(0),
+            "- Compare [1,1,3,1,1] vs [1,1,5,1,1]
+  - Compare 1 vs 1
+  - Compare 1 vs 1
+  - Compare 3 vs 5
+    - Left side is smaller, so inputs are in the right order
+"
+        );
+        assert_eq!(
+            explain_pair(1),
+            "- Compare [[1],[2,3,4]] vs [[1],4]
+  - Compare [1] vs [1]
+    - Compare 1 vs 1
+  - Compare [2,3,4] vs 4
+    - Mixed types; convert right to [4] and retry comparison
+    - Compare [2,3,4] vs [4]
+      - Compare 2 vs 4
+        - Left side is smaller, so inputs are in the right order
+"
+        );
+        assert_eq!(
+            explain_pair(2),
+            "- Compare [9] vs [[8,7,6]]
+  - Compare 9 vs [8,7,6]
+    - Mixed types; convert left to [9] and retry comparison
+    - Compare [9] vs [8,7,6]
+      - Compare 9 vs 8
+        - Right side is smaller, so inputs are not in the right order
+"
+        );
+        assert_eq!(
+            explain_pair(4),
+            "- Compare [7,7,7,7] vs [7,7,7]
+  - Compare 7 vs 7
+  - Compare 7 vs 7
+  - Compare 7 vs 7
+  - Right side ran out of items, so inputs are not in the right order
+"
+        );
+        assert_eq!(
+            explain_pair(6),
+            "- Compare [[[]]] vs [[]]
+  - Compare [[]] vs []
+    - Right side ran out of items, so inputs are not in the right order
+"
+        );
+    }
+
+    #[test]
+    fn test_explanations_agree() {
+        // the last line of the explanation says what the comparison decided
+        for (left, right) in parse_pairs(TEST_INPUT).unwrap() {
+            let explanation = explain_cmp(&left, &right);
+            let verdict = explanation.lines().last().unwrap();
+            match packet_cmp(&left, &right) {
+                Ordering::Less => assert!(verdict.ends_with("inputs are in the right order")),
+                Ordering::Greater => assert!(verdict.ends_with("not in the right order")),
+                Ordering::Equal => assert!(verdict.contains("Compare")),
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Divider packets, and where they end up when they're sorted in with the other packets.
+//!
+//! A divider's position is one more than the number of packets (and other dividers) that are
+//! smaller than it, so there's no need to sort anything to find it. That only works if no
+//! packet compares equal to a divider, because then it isn't clear which one goes first, so
+//! that's an error. "Equal" here is the puzzle's idea of equal, so `[2]` is equal to `[[2]]`.
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+use parse_display::{Display, FromStr};
+
+use crate::compare::packet_cmp;
+use crate::Value;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DividerError {
+    /// The same divider was given twice.
+    DuplicateDivider(Value),
+    /// This packet (counting from 1) compares equal to a divider.
+    Ambiguous { packet: usize, divider: Value },
+    /// The decoder key is too big to compute.
+    Overflow,
+}
+
+impl fmt::Display for DividerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DividerError::DuplicateDivider(divider) => write!(f, "divider {} is given twice", divider),
+            DividerError::Ambiguous { packet, divider } => write!(
+                f,
+                "packet {} is equal to divider {}, so the divider's position is ambiguous",
+                packet, divider
+            ),
+            DividerError::Overflow => write!(f, "the decoder key is too big"),
+        }
+    }
+}
+
+impl Error for DividerError {}
+
+/// How to turn the dividers' positions into a decoder key.
+#[derive(Display, FromStr, PartialEq, Eq, Debug, Clone, Copy)]
+#[display(style = "lowercase")]
+pub enum KeyStyle {
+    Sum,
+    /// What the puzzle asks for.
+    Product,
+    /// Just list the positions, in the order the dividers were given.
+    Positions,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DecoderKey {
+    Number(u128),
+    Positions(Vec<usize>),
+}
+
+impl fmt::Display for DecoderKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecoderKey::Number(n) => write!(f, "{}", n),
+            DecoderKey::Positions(positions) => {
+                let positions: Vec<String> = positions.iter().map(|pos| pos.to_string()).collect();
+                write!(f, "{}", positions.join(","))
+            }
+        }
+    }
+}
+
+impl KeyStyle {
+    pub fn decoder_key(&self, positions: &[usize]) -> Result<DecoderKey, DividerError> {
+        let mut numbers = positions.iter().map(|&pos| pos as u128);
+        match self {
+            KeyStyle::Sum => numbers
+                .try_fold(0u128, |total, pos| total.checked_add(pos))
+                .map(DecoderKey::Number)
+                .ok_or(DividerError::Overflow),
+            KeyStyle::Product => numbers
+                .try_fold(1u128, |total, pos| total.checked_mul(pos))
+                .map(DecoderKey::Number)
+                .ok_or(DividerError::Overflow),
+            KeyStyle::Positions => Ok(DecoderKey::Positions(positions.to_vec())),
+        }
+    }
+}
+
+/// Counts how many packets are smaller than each divider, one packet at a time.
+pub struct DividerCounter<'a> {
+    dividers: &'a [Value],
+    smaller: Vec<usize>,
+}
+
+impl<'a> DividerCounter<'a> {
+    pub fn new(dividers: &'a [Value]) -> Result<Self, DividerError> {
+        for (idx, divider) in dividers.iter().enumerate() {
+            if dividers[..idx].iter().any(|other| packet_cmp(other, divider) == Ordering::Equal) {
+                return Err(DividerError::DuplicateDivider(divider.clone()));
+            }
+        }
+        // the dividers get sorted in too, so each one counts the dividers smaller than it
+        let smaller = dividers
+            .iter()
+            .map(|divider| {
+                dividers.iter().filter(|other| packet_cmp(other, divider) == Ordering::Less).count()
+            })
+            .collect();
+        Ok(DividerCounter { dividers, smaller })
+    }
+
+    /// Count a packet, which is packet number `packet_num` in the input.
+    pub fn add(&mut self, packet: &Value, packet_num: usize) -> Result<(), DividerError> {
+        for (count, divider) in self.smaller.iter_mut().zip(self.dividers) {
+            match packet_cmp(packet, divider) {
+                Ordering::Less => *count += 1,
+                Ordering::Equal => {
+                    return Err(DividerError::Ambiguous { packet: packet_num, divider: divider.clone() })
+                }
+                Ordering::Greater => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The 1-based position of each divider in the sorted packets, in the order the dividers
+    /// were given.
+    pub fn positions(&self) -> Vec<usize> {
+        self.smaller.iter().map(|count| count + 1).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse_packet, parse_packets};
+    use crate::TEST_INPUT;
+
+    fn packets(texts: &[&str]) -> Vec<Value> {
+        texts.iter().map(|text| parse_packet(text).unwrap()).collect()
+    }
+
+    fn positions(dividers: &[Value]) -> Result<Vec<usize>, DividerError> {
+        let mut counter = DividerCounter::new(dividers)?;
+        for (idx, packet) in parse_packets(TEST_INPUT).unwrap().iter().enumerate() {
+            counter.add(packet, idx + 1)?;
+        }
+        Ok(counter.positions())
+    }
+
+    #[test]
+    fn test_positions() {
+        assert_eq!(positions(&packets(&["[[2]]", "[[6]]"])), Ok(vec![10, 14]));
+        assert_eq!(positions(&packets(&["[[6]]", "[10]", "[[2]]", "[0]"])), Ok(vec![15, 20, 11, 4]));
+        assert_eq!(positions(&[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            positions(&packets(&["[[2]]", "[9]"])),
+            Err(DividerError::Ambiguous { packet: 5, divider: parse_packet("[9]").unwrap() })
+        );
+        assert_eq!(
+            positions(&packets(&["[[2]]", "[3]", "[[2]]"])),
+            Err(DividerError::DuplicateDivider(parse_packet("[[2]]").unwrap()))
+        );
+        // [[[9]]] isn't the same as [9], but the puzzle can't tell them apart
+        assert_eq!(
+            positions(&packets(&["[[[9]]]"])),
+            Err(DividerError::Ambiguous { packet: 5, divider: parse_packet("[[[9]]]").unwrap() })
+        );
+    }
+
+    #[test]
+    fn test_key_styles() {
+        assert_eq!("sum".parse(), Ok(KeyStyle::Sum));
+        assert_eq!(KeyStyle::Sum.decoder_key(&[10, 14]), Ok(DecoderKey::Number(24)));
+        assert_eq!(KeyStyle::Product.decoder_key(&[10, 14]), Ok(DecoderKey::Number(140)));
+        assert_eq!(KeyStyle::Product.decoder_key(&[]), Ok(DecoderKey::Number(1)));
+        assert_eq!(KeyStyle::Positions.decoder_key(&[10, 14]).unwrap().to_string(), "10,14");
+        assert_eq!(KeyStyle::Product.decoder_key(&[usize::MAX; 3]), Err(DividerError::Overflow));
+    }
+}
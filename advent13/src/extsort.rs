@@ -0,0 +1,248 @@
+//! Working with packet files that are too big to hold in memory.
+//!
+//! `external_sort` sorts a file of packets by sorting chunks of it that fit in memory, spilling
+//! each sorted chunk to a temporary file, and then merging all the chunks. `divider_positions`
+//! finds where divider packets would end up in the sorted file without sorting anything, by
+//! counting how many packets are smaller than each divider.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{self, AtomicUsize};
+
+use crate::dividers::{DividerCounter, DividerError};
+use crate::parse::{parse_line, PacketError};
+use crate::Value;
+
+#[derive(Debug)]
+pub enum SortError {
+    Io(io::Error),
+    Packet(PacketError),
+    Divider(DividerError),
+    /// A chunk size of 0, which would never hold anything.
+    ZeroChunkSize,
+}
+
+impl fmt::Display for SortError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SortError::Io(err) => write!(f, "{}", err),
+            SortError::Packet(err) => write!(f, "{}", err),
+            SortError::Divider(err) => write!(f, "{}", err),
+            SortError::ZeroChunkSize => write!(f, "chunks need to hold at least one packet"),
+        }
+    }
+}
+
+impl Error for SortError {}
+
+impl From<io::Error> for SortError {
+    fn from(err: io::Error) -> Self {
+        SortError::Io(err)
+    }
+}
+
+impl From<PacketError> for SortError {
+    fn from(err: PacketError) -> Self {
+        SortError::Packet(err)
+    }
+}
+
+impl From<DividerError> for SortError {
+    fn from(err: DividerError) -> Self {
+        SortError::Divider(err)
+    }
+}
+
+/// Reads packets one line at a time, skipping blank lines.
+pub struct PacketReader<R> {
+    lines: io::Lines<R>,
+    line: usize,
+    packet: usize,
+}
+
+impl<R: BufRead> PacketReader<R> {
+    pub fn new(reader: R) -> Self {
+        PacketReader { lines: reader.lines(), line: 0, packet: 0 }
+    }
+}
+
+impl<R: BufRead> Iterator for PacketReader<R> {
+    type Item = Result<Value, SortError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let text = match self.lines.next()? {
+                Ok(text) => text,
+                Err(err) => return Some(Err(err.into())),
+            };
+            self.line += 1;
+            if !text.trim().is_empty() {
+                self.packet += 1;
+                return Some(parse_line(&text, self.line, self.packet).map_err(SortError::from));
+            }
+        }
+    }
+}
+
+/// A temporary directory for the sorted chunks, which gets deleted along with everything in it
+/// when we're done, even if sorting fails partway.
+struct SpillDir {
+    path: PathBuf,
+    num_files: usize,
+}
+
+/// Makes the directory name unique when there's more than one sort going on at once.
+static NEXT_SPILL_DIR: AtomicUsize = AtomicUsize::new(0);
+
+impl SpillDir {
+    fn new(parent: &Path) -> io::Result<Self> {
+        let seq = NEXT_SPILL_DIR.fetch_add(1, atomic::Ordering::Relaxed);
+        let path = parent.join(format!("advent13-sort-{}-{}", process::id(), seq));
+        fs::create_dir_all(&path)?;
+        Ok(SpillDir { path, num_files: 0 })
+    }
+
+    /// Write a sorted chunk to a new file, one packet per line.
+    fn spill(&mut self, packets: &[Value]) -> io::Result<PathBuf> {
+        let path = self.path.join(format!("chunk-{}.txt", self.num_files));
+        self.num_files += 1;
+        let mut out = BufWriter::new(File::create(&path)?);
+        for packet in packets {
+            writeln!(out, "{}", packet)?;
+        }
+        out.flush()?;
+        Ok(path)
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        // there's nothing useful to do if this fails, besides leaving the files behind
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Sort the packets from `input`, writing them to `output` one per line. At most `chunk_size`
+/// packets are held in memory while reading; the sorted chunks go in a temporary directory
+/// inside `spill_dir`. Returns how many packets there were.
+pub fn external_sort(
+    input: impl BufRead,
+    mut output: impl Write,
+    chunk_size: usize,
+    spill_dir: &Path,
+) -> Result<usize, SortError> {
+    if chunk_size == 0 {
+        return Err(SortError::ZeroChunkSize);
+    }
+    let mut spill = SpillDir::new(spill_dir)?;
+    let mut chunk_paths: Vec<PathBuf> = Vec::new();
+    // the chunk size can be huge, so let the chunk grow as needed
+    let mut chunk: Vec<Value> = Vec::new();
+    let mut num_packets = 0;
+    for packet in PacketReader::new(input) {
+        chunk.push(packet?);
+        num_packets += 1;
+        if chunk.len() == chunk_size {
+            chunk.sort();
+            chunk_paths.push(spill.spill(&chunk)?);
+            chunk.clear();
+        }
+    }
+    if !chunk.is_empty() {
+        chunk.sort();
+        chunk_paths.push(spill.spill(&chunk)?);
+    }
+    drop(chunk);
+
+    // k-way merge: the heap holds the smallest packet left in each chunk. Ties go to the
+    // earlier chunk, so equal packets stay in the order they were in the input.
+    let mut readers: Vec<PacketReader<BufReader<File>>> = Vec::new();
+    let mut heap: BinaryHeap<Reverse<(Value, usize)>> = BinaryHeap::new();
+    for (chunk_idx, path) in chunk_paths.iter().enumerate() {
+        let mut reader = PacketReader::new(BufReader::new(File::open(path)?));
+        if let Some(packet) = reader.next() {
+            heap.push(Reverse((packet?, chunk_idx)));
+        }
+        readers.push(reader);
+    }
+    while let Some(Reverse((packet, chunk_idx))) = heap.pop() {
+        writeln!(output, "{}", packet)?;
+        if let Some(next) = readers[chunk_idx].next() {
+            heap.push(Reverse((next?, chunk_idx)));
+        }
+    }
+    output.flush()?;
+    Ok(num_packets)
+}
+
+/// Find the 1-based position each divider would have if the dividers were added to the packets
+/// from `input` and everything was sorted. Only one packet is in memory at a time.
+pub fn divider_positions(input: impl BufRead, dividers: &[Value]) -> Result<Vec<usize>, SortError> {
+    let mut counter = DividerCounter::new(dividers)?;
+    for (idx, packet) in PacketReader::new(input).enumerate() {
+        counter.add(&packet?, idx + 1)?;
+    }
+    Ok(counter.positions())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{parse_packet, parse_packets};
+    use crate::TEST_INPUT;
+    use std::env;
+
+    /// A file of packets in no particular order, with plenty of duplicates.
+    fn shuffled_packets() -> String {
+        let lines: Vec<String> = (0..500)
+            .map(|i: usize| format!("[{},[{}],{}]", (i * 7919) % 101, i % 13, i % 3))
+            .collect();
+        lines.join("\n")
+    }
+
+    fn sort_to_string(input: &str, chunk_size: usize) -> String {
+        let mut output = Vec::new();
+        external_sort(input.as_bytes(), &mut output, chunk_size, &env::temp_dir()).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_external_sort() {
+        for input in [TEST_INPUT.to_owned(), shuffled_packets()] {
+            let mut expected = parse_packets(&input).unwrap();
+            expected.sort();
+            let expected: String = expected.iter().map(|packet| format!("{}\n", packet)).collect();
+            for chunk_size in [1, 3, 7, 1000] {
+                assert_eq!(sort_to_string(&input, chunk_size), expected, "chunk size {}", chunk_size);
+            }
+        }
+        assert_eq!(sort_to_string("", 5), "");
+    }
+
+    #[test]
+    fn test_sort_errors() {
+        let mut output = Vec::new();
+        let result = external_sort("[1]\n\n[2]\n[3,\n".as_bytes(), &mut output, 1, &env::temp_dir());
+        match result {
+            Err(SortError::Packet(err)) => assert_eq!((err.line, err.packet), (4, 3)),
+            other => panic!("expected a packet error, got {:?}", other),
+        }
+        let result = external_sort("[1]".as_bytes(), &mut output, 0, &env::temp_dir());
+        assert!(matches!(result, Err(SortError::ZeroChunkSize)));
+        assert_eq!(sort_to_string("[2]\n[1]", usize::MAX), "[1]\n[2]\n");
+    }
+
+    #[test]
+    fn test_divider_positions() {
+        let dividers = vec![parse_packet("[[2]]").unwrap(), parse_packet("[[6]]").unwrap()];
+        assert_eq!(divider_positions(TEST_INPUT.as_bytes(), &dividers).unwrap(), vec![10, 14]);
+        // the order of the dividers doesn't matter
+        let reversed: Vec<Value> = dividers.iter().rev().cloned().collect();
+        assert_eq!(divider_positions(TEST_INPUT.as_bytes(), &reversed).unwrap(), vec![14, 10]);
+    }
+}
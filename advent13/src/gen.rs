@@ -0,0 +1,199 @@
+//! Making up random packets, for testing and for stress-testing with big files.
+//!
+//! The random numbers come from a splitmix64 generator, so the same seed always gives the same
+//! packets.
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::Value;
+
+/// The most deeply lists can be nested. Making packets is recursive, so this keeps it from
+/// running out of stack.
+pub const MAX_DEPTH: u32 = 1000;
+
+/// The most items a list can have. Packets can still get enormous when the width and the depth
+/// are both big, but this keeps the numbers from overflowing.
+pub const MAX_WIDTH: usize = 10_000;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum GenError {
+    TooDeep(u32),
+    TooWide(usize),
+    NoInts(RangeInclusive<i32>),
+}
+
+impl fmt::Display for GenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GenError::TooDeep(depth) => {
+                write!(f, "lists can be nested at most {} deep, not {}", MAX_DEPTH, depth)
+            }
+            GenError::TooWide(width) => {
+                write!(f, "lists can have at most {} items, not {}", MAX_WIDTH, width)
+            }
+            GenError::NoInts(ints) => {
+                write!(f, "there are no ints from {} to {}", ints.start(), ints.end())
+            }
+        }
+    }
+}
+
+impl Error for GenError {}
+
+pub struct PacketGen {
+    state: u64,
+    max_depth: u32,
+    max_width: usize,
+    ints: RangeInclusive<i32>,
+}
+
+impl PacketGen {
+    /// A generator with small packets: lists nested up to 4 deep, up to 4 items each, and ints
+    /// from 0 to 10 like the puzzle has.
+    pub fn new(seed: u64) -> Self {
+        PacketGen { state: seed, max_depth: 4, max_width: 4, ints: 0..=10 }
+    }
+
+    /// How deeply lists can be nested inside the outer list, up to `MAX_DEPTH`.
+    pub fn depth(mut self, max_depth: u32) -> Result<Self, GenError> {
+        if max_depth > MAX_DEPTH {
+            return Err(GenError::TooDeep(max_depth));
+        }
+        self.max_depth = max_depth;
+        Ok(self)
+    }
+
+    /// The most items a list can have, up to `MAX_WIDTH`.
+    pub fn width(mut self, max_width: usize) -> Result<Self, GenError> {
+        if max_width > MAX_WIDTH {
+            return Err(GenError::TooWide(max_width));
+        }
+        self.max_width = max_width;
+        Ok(self)
+    }
+
+    /// The range that ints get picked from, which can't be empty.
+    pub fn ints(mut self, ints: RangeInclusive<i32>) -> Result<Self, GenError> {
+        if ints.is_empty() {
+            return Err(GenError::NoInts(ints));
+        }
+        self.ints = ints;
+        Ok(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A random number from 0 to `n - 1`. (It's very slightly biased, which doesn't matter here.)
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    fn int(&mut self) -> Value {
+        let (low, high) = (*self.ints.start() as i64, *self.ints.end() as i64);
+        Value::Int((low + self.below((high - low + 1) as u64) as i64) as i32)
+    }
+
+    fn value(&mut self, depth: u32) -> Value {
+        if depth == 0 || self.below(3) == 0 {
+            self.int()
+        } else {
+            self.list(depth)
+        }
+    }
+
+    fn list(&mut self, depth: u32) -> Value {
+        let len = self.below(self.max_width as u64 + 1);
+        Value::Array((0..len).map(|_| self.value(depth - 1)).collect())
+    }
+
+    /// Make a packet. Like in the puzzle, the outside of a packet is always a list.
+    pub fn packet(&mut self) -> Value {
+        self.list(self.max_depth + 1)
+    }
+
+    /// Make a value that might just be an int, which is useful for testing comparisons.
+    #[cfg(test)]
+    pub fn value_or_packet(&mut self) -> Value {
+        self.value(self.max_depth + 1)
+    }
+}
+
+impl Iterator for PacketGen {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        Some(self.packet())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(value: &Value) -> u32 {
+        match value {
+            Value::Int(_) => 0,
+            Value::Array(items) => 1 + items.iter().map(depth).max().unwrap_or(0),
+        }
+    }
+
+    fn ints(value: &Value) -> Vec<i32> {
+        match value {
+            Value::Int(n) => vec![*n],
+            Value::Array(items) => items.iter().flat_map(ints).collect(),
+        }
+    }
+
+    fn max_width(value: &Value) -> usize {
+        match value {
+            Value::Int(_) => 0,
+            Value::Array(items) => items.iter().map(max_width).max().unwrap_or(0).max(items.len()),
+        }
+    }
+
+    #[test]
+    fn test_seeds() {
+        let first: Vec<Value> = PacketGen::new(7).take(20).collect();
+        let again: Vec<Value> = PacketGen::new(7).take(20).collect();
+        let other: Vec<Value> = PacketGen::new(8).take(20).collect();
+        assert_eq!(first, again);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_limits() {
+        let packets = PacketGen::new(1).depth(2).unwrap().width(3).unwrap().ints(-5..=5).unwrap();
+        let packets: Vec<Value> = packets.take(500).collect();
+        assert!(packets.iter().all(|packet| matches!(packet, Value::Array(_))));
+        assert!(packets.iter().all(|packet| depth(packet) <= 3));
+        assert!(packets.iter().any(|packet| depth(packet) == 3));
+        assert!(packets.iter().all(|packet| max_width(packet) <= 3));
+        let all_ints: Vec<i32> = packets.iter().flat_map(ints).collect();
+        assert!(all_ints.iter().all(|n| (-5..=5).contains(n)));
+        assert!(all_ints.contains(&-5) && all_ints.contains(&5));
+
+        let extremes = PacketGen::new(2).ints(i32::MIN..=i32::MAX).unwrap();
+        let extremes: Vec<Value> = extremes.take(50).collect();
+        assert!(extremes.iter().flat_map(ints).any(|n| n < 0));
+    }
+
+    #[test]
+    fn test_bad_limits() {
+        assert_eq!(PacketGen::new(1).width(usize::MAX).err(), Some(GenError::TooWide(usize::MAX)));
+        assert_eq!(PacketGen::new(1).depth(u32::MAX).err(), Some(GenError::TooDeep(u32::MAX)));
+        let (low, high) = (3, 2);
+        assert_eq!(PacketGen::new(1).ints(low..=high).err(), Some(GenError::NoInts(low..=high)));
+        // the biggest allowed values work, one at a time
+        let mut wide = PacketGen::new(1).width(MAX_WIDTH).unwrap().depth(0).unwrap();
+        assert!(max_width(&wide.packet()) <= MAX_WIDTH);
+        let mut deep = PacketGen::new(1).width(1).unwrap().depth(MAX_DEPTH).unwrap();
+        assert!(depth(&deep.packet()) <= MAX_DEPTH + 1);
+    }
+}
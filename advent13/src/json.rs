@@ -0,0 +1,422 @@
+//! A small JSON value model, so packets can be passed to and from other tools.
+//!
+//! Packets are almost JSON already: a packet is a JSON array whose elements are integers or
+//! more arrays. This covers all of JSON, so that converting to a packet can say what's wrong
+//! with a document that isn't one.
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Value;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Keys and values in the order they were written.
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum JsonError {
+    /// The text isn't valid JSON. The offset is in bytes.
+    Syntax { offset: usize, message: String },
+    /// The JSON is valid, but isn't a packet.
+    NotAPacket(String),
+    /// JSON has no way to write infinity or NaN. This is how Rust writes the number.
+    NotFinite(String),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonError::Syntax { offset, message } => {
+                write!(f, "invalid JSON at offset {}: {}", offset, message)
+            }
+            JsonError::NotAPacket(message) => write!(f, "JSON isn't a packet: {}", message),
+            JsonError::NotFinite(n) => write!(f, "{} can't be written as JSON", n),
+        }
+    }
+}
+
+impl Error for JsonError {}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for ch in s.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            ch if (ch as u32) < 0x20 => write!(f, "\\u{:04x}", ch as u32)?,
+            ch => write!(f, "{}", ch)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl Json {
+    /// Write compact JSON, or fail if there's a number that JSON can't represent.
+    pub fn to_json(&self) -> Result<String, JsonError> {
+        self.check_finite()?;
+        Ok(self.to_string())
+    }
+
+    fn check_finite(&self) -> Result<(), JsonError> {
+        match self {
+            Json::Number(n) if !n.is_finite() => Err(JsonError::NotFinite(n.to_string())),
+            Json::Array(items) => items.iter().try_for_each(Json::check_finite),
+            Json::Object(entries) => entries.iter().try_for_each(|(_, value)| value.check_finite()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    /// Write compact JSON, with no whitespace. Infinity and NaN can't be written, so they make
+    /// this fail; `to_json` says what went wrong instead.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if !n.is_finite() => Err(fmt::Error),
+            // Rust writes whole numbers without a decimal point, which is what JSON tools expect,
+            // and never uses an exponent, so the result is always a valid JSON number
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(entries) => {
+                write!(f, "{{")?;
+                for (idx, (key, value)) in entries.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// A recursive-descent JSON parser, working on bytes since all of JSON's syntax is ASCII.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, JsonError> {
+        Err(JsonError::Syntax { offset: self.pos, message: message.to_owned() })
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.as_bytes().get(self.pos) {
+            self.pos += 1;
+        }
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    fn eat(&mut self, byte: u8) -> Result<(), JsonError> {
+        if self.peek() != Some(byte) {
+            return self.error(&format!("expected '{}'", byte as char));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        if !self.input[self.pos..].starts_with(word) {
+            return self.error(&format!("expected '{}'", word));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        match self.peek() {
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return self.error("expected ',' or ']'"),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(entries));
+                }
+                loop {
+                    if self.peek() != Some(b'"') {
+                        return self.error("expected a string key");
+                    }
+                    let key = self.string()?;
+                    self.eat(b':')?;
+                    entries.push((key, self.value()?));
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(entries));
+                        }
+                        _ => return self.error("expected ',' or '}'"),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => self.error("expected a JSON value"),
+            None => self.error("unexpected end of input"),
+        }
+    }
+
+    /// Skip over a run of digits, and say whether there were any.
+    fn digits(&mut self) -> bool {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.input.as_bytes().get(self.pos) {
+            self.pos += 1;
+        }
+        self.pos > start
+    }
+
+    /// Parse a number, following JSON's grammar: an optional minus sign, then either 0 or digits
+    /// that don't start with 0, then optionally a fraction and an exponent, each of which needs
+    /// at least one digit.
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        let bytes = self.input.as_bytes();
+        if bytes.get(self.pos) == Some(&b'-') {
+            self.pos += 1;
+        }
+        match bytes.get(self.pos) {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                self.digits();
+            }
+            _ => return self.error("expected a digit"),
+        }
+        if let Some(b'0'..=b'9') = bytes.get(self.pos) {
+            return self.error("numbers can't have leading zeros");
+        }
+        if bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            if !self.digits() {
+                return self.error("expected a digit after the decimal point");
+            }
+        }
+        if let Some(b'e' | b'E') = bytes.get(self.pos) {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = bytes.get(self.pos) {
+                self.pos += 1;
+            }
+            if !self.digits() {
+                return self.error("expected a digit in the exponent");
+            }
+        }
+        // the grammar allows any number of digits, but an f64 only goes so high
+        match self.input[start..self.pos].parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Json::Number(n)),
+            _ => {
+                self.pos = start;
+                self.error("number is too big")
+            }
+        }
+    }
+
+    /// Parse a string, starting at its opening quote.
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.pos += 1;
+        let mut result = String::new();
+        loop {
+            let rest = &self.input[self.pos..];
+            let ch = match rest.chars().next() {
+                Some(ch) => ch,
+                None => return self.error("unterminated string"),
+            };
+            self.pos += ch.len_utf8();
+            match ch {
+                '"' => return Ok(result),
+                '\\' => {
+                    let escaped = match self.input.as_bytes().get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            // surrogate pairs aren't supported, since packets don't have strings
+                            let hex = self.input.get(self.pos + 1..self.pos + 5);
+                            match hex.and_then(|hex| u32::from_str_radix(hex, 16).ok()).and_then(char::from_u32) {
+                                Some(ch) => {
+                                    self.pos += 4;
+                                    ch
+                                }
+                                None => return self.error("invalid unicode escape"),
+                            }
+                        }
+                        _ => return self.error("invalid escape"),
+                    };
+                    self.pos += 1;
+                    result.push(escaped);
+                }
+                ch => result.push(ch),
+            }
+        }
+    }
+}
+
+impl FromStr for Json {
+    type Err = JsonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let value = parser.value()?;
+        if parser.peek().is_some() {
+            return parser.error("unexpected input after the JSON value");
+        }
+        Ok(value)
+    }
+}
+
+impl From<&Value> for Json {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Int(n) => Json::Number(*n as f64),
+            Value::Array(items) => Json::Array(items.iter().map(Json::from).collect()),
+        }
+    }
+}
+
+impl TryFrom<&Json> for Value {
+    type Error = JsonError;
+
+    fn try_from(json: &Json) -> Result<Self, Self::Error> {
+        match json {
+            Json::Number(n) => {
+                if n.fract() == 0.0 && *n >= i32::MIN as f64 && *n <= i32::MAX as f64 {
+                    Ok(Value::Int(*n as i32))
+                } else {
+                    Err(JsonError::NotAPacket(format!("{} isn't a 32-bit integer", n)))
+                }
+            }
+            Json::Array(items) => {
+                Ok(Value::Array(items.iter().map(Value::try_from).collect::<Result<_, _>>()?))
+            }
+            other => Err(JsonError::NotAPacket(format!("{} isn't a number or a list", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_packet;
+
+    #[test]
+    fn test_json_syntax() {
+        let json: Json = r#" {"a": [1, -2.5e1, true, null], "b\n": "x\"éy"} "#.parse().unwrap();
+        assert_eq!(
+            json,
+            Json::Object(vec![
+                (
+                    "a".to_owned(),
+                    Json::Array(vec![Json::Number(1.0), Json::Number(-25.0), Json::Bool(true), Json::Null])
+                ),
+                ("b\n".to_owned(), Json::String("x\"éy".to_owned())),
+            ])
+        );
+        assert_eq!(json.to_string(), r#"{"a":[1,-25,true,null],"b\n":"x\"éy"}"#);
+        assert_eq!(json.to_string().parse::<Json>(), Ok(json));
+
+        assert_eq!(
+            "[1,]".parse::<Json>(),
+            Err(JsonError::Syntax { offset: 3, message: "expected a JSON value".to_owned() })
+        );
+        assert!("[1] 2".parse::<Json>().is_err());
+        assert!("\"abc".parse::<Json>().is_err());
+        assert!("{1: 2}".parse::<Json>().is_err());
+    }
+
+    #[test]
+    fn test_numbers() {
+        let valid = ["0", "-0", "7", "-12", "0.5", "-0.25", "10.75", "1e3", "1E+2", "-1.25e-3"];
+        for text in valid {
+            let json: Json = text.parse().unwrap_or_else(|err| panic!("{}: {}", text, err));
+            assert_eq!(json, Json::Number(text.parse().unwrap()), "{}", text);
+            // it comes back the same, though maybe written differently
+            assert_eq!(json.to_json().unwrap().parse::<Json>(), Ok(json.clone()), "{}", text);
+        }
+        let invalid = ["01", "-01", "00", "-", "--1", "+1", ".5", "-.5", "1.", "1.e5", "1e", "1e+"];
+        for text in invalid.iter().chain(&["1e999", "2e00x"]) {
+            assert!(matches!(text.parse::<Json>(), Err(JsonError::Syntax { .. })), "{}", text);
+        }
+        let err = "[01]".parse::<Json>().unwrap_err();
+        assert_eq!(err.to_string(), "invalid JSON at offset 2: numbers can't have leading zeros");
+        let err = "[1.]".parse::<Json>().unwrap_err();
+        let expected = "invalid JSON at offset 3: expected a digit after the decimal point";
+        assert_eq!(err.to_string(), expected);
+
+        // very big and very small numbers are written out in full, which is still valid JSON
+        for n in [1e300, -2.5e-10, f64::MAX, f64::MIN_POSITIVE] {
+            let json = Json::Array(vec![Json::Number(n)]);
+            assert_eq!(json.to_json().unwrap().parse::<Json>(), Ok(json));
+        }
+        for n in [f64::INFINITY, f64::NEG_INFINITY] {
+            let json = Json::Object(vec![("a".to_owned(), Json::Array(vec![Json::Number(n)]))]);
+            assert_eq!(json.to_json(), Err(JsonError::NotFinite(n.to_string())));
+        }
+        let err = Json::Number(f64::NAN).to_json().unwrap_err();
+        assert_eq!(err.to_string(), "NaN can't be written as JSON");
+    }
+
+    #[test]
+    fn test_packets() {
+        let packet = parse_packet("[1,[2,[-3,[]]],4]").unwrap();
+        let json = Json::from(&packet);
+        assert_eq!(json.to_string(), "[1,[2,[-3,[]]],4]");
+        assert_eq!(Value::try_from(&json), Ok(packet));
+        assert_eq!(Value::try_from(&"[1.0, 2e0]".parse::<Json>().unwrap()).unwrap().to_string(), "[1,2]");
+
+        let not_packets = ["[1.5]", "[\"a\"]", "[{}]", "[3000000000]", "[null]"];
+        for text in not_packets {
+            let json: Json = text.parse().unwrap();
+            assert!(matches!(Value::try_from(&json), Err(JsonError::NotAPacket(_))), "{}", text);
+        }
+    }
+}
@@ -1,13 +1,20 @@
 use std::cmp::{Ord, Ordering};
-use std::fs;
-use nom::{
-    branch::alt,
-    character::complete::char,
-    combinator,
-    multi::separated_list0,
-    sequence::{preceded, terminated},
-    IResult,
-};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+
+mod compare;
+mod dividers;
+mod extsort;
+mod gen;
+mod json;
+mod parse;
+use dividers::{DecoderKey, DividerCounter, KeyStyle};
+use gen::PacketGen;
+use json::{Json, JsonError};
+use parse::{parse_packet, parse_packets, parse_pairs, PacketError};
 
 
 pub const TEST_PAIR: &str = "[[1],[2,3,4]]
@@ -39,28 +46,36 @@ pub const TEST_INPUT: &str = "[1,1,3,1,1]
 [1,[2,[3,[4,[5,6,0]]]],8,9]";
 
 #[derive(Debug, PartialEq, Eq, Clone)]
-enum Value {
+pub enum Value {
     Int(i32),
     Array(Vec<Value>)
 }
 
-impl Value {
-    fn to_array(&self) -> Vec<Value> {
-        match &self {
-            Value::Int(n) => vec![Value::Int(*n)],
-            Value::Array(arr) => arr.clone()
+impl fmt::Display for Value {
+    /// Write the packet the way the puzzle does, like `[1,[2,3]]`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
         }
     }
 }
 
 impl Ord for Value {
+    /// Packets are ordered the way the puzzle says. The puzzle treats an int and a list holding
+    /// just that int as equal, but they're different values, so to be a real total order that
+    /// agrees with `==`, ties like that are broken by their structure.
     fn cmp(&self, other: &Self) -> Ordering {
-        if let Value::Int(my_int) = self {
-            if let Value::Int(other_int) = other {
-                return my_int.cmp(other_int);
-            }
-        }
-        self.to_array().cmp(&other.to_array())
+        compare::packet_cmp(self, other).then_with(|| compare::structural_cmp(self, other))
     }
 }
 
@@ -70,82 +85,125 @@ impl PartialOrd for Value {
     }
 }
 
-// parse either an int or an array
-fn value<'a>(i: &'a str) -> IResult<&'a str, Value> {
-    // skip a blank line before an array (useful in the outermost array)
-    let (i, _) = combinator::opt(char('\n'))(i)?;
-    alt((
-        combinator::map(array, Value::Array),
-        combinator::map(nom::character::complete::i32, Value::Int)
-    ))(i)
-}
-
-fn array<'a>(i: &'a str) -> IResult<&'a str, Vec<Value>> {
-    preceded(
-        char('['),
-        terminated(
-            separated_list0(char(','), value),
-            char(']')
-        )
-    )(i)
+fn sum_ordered(input: &str) -> Result<i32, PacketError> {
+    let mut sum = 0;
+    for (idx, (first, second)) in parse_pairs(input)?.into_iter().enumerate() {
+        if compare::packet_cmp(&first, &second) != Ordering::Greater {
+            sum += idx as i32 + 1;
+        }
+    }
+    Ok(sum)
 }
 
-fn parse_value_exact(i: &str) -> Value {
-    let (i, value) = value(i).unwrap();
-    if i.len() > 0 {
-        panic!("There was leftover input: {}", i);
+/// Find where the divider packets go when they're sorted in with the packets from the input,
+/// and combine their positions into a decoder key.
+fn find_divider_packets(input: &str, dividers: &[Value], style: KeyStyle) -> Result<DecoderKey, Box<dyn Error>> {
+    let mut counter = DividerCounter::new(dividers)?;
+    for (idx, packet) in parse_packets(input)?.iter().enumerate() {
+        counter.add(packet, idx + 1)?;
     }
-    value
+    Ok(style.decoder_key(&counter.positions())?)
 }
 
-fn input_pair_parser<'a>(i: &'a str) -> IResult<&'a str, (Value, Value)> {
-    let (i, first) = value(i)?;
-    let (i, _) = char('\n')(i)?;
-    let (i, second) = value(i)?;
-    Ok((i, (first, second)))
-}
 
-fn input_list_parser<'a>(i: &'a str) -> IResult<&'a str, Vec<Value>> {
-    separated_list0(char('\n'), value)(i)
-}
+fn main() -> Result<(), Box<dyn Error>> {
+    // some modes read their own packets, so input.txt only has to exist for the ones that use it
+    let read_input = || fs::read_to_string("input.txt");
 
-fn sum_ordered(input: &str) -> i32 {
-    let mut sum = 0;
-    for (idx, pair_input) in input.split("\n\n").enumerate() {
-        let (remaining, (first, second)) = input_pair_parser(pair_input).unwrap();
-        if remaining.trim().len() > 0 {
-            panic!("There was input left over: {}", remaining);
+    // `--to-json` writes the input's packets as a JSON list
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--to-json") {
+        let packets = parse_packets(&read_input()?)?;
+        println!("{}", Json::Array(packets.iter().map(Json::from).collect()).to_json()?);
+        return Ok(());
+    }
+
+    // `--divider PACKET`, as many times as you like, replaces the puzzle's divider packets, and
+    // `--key sum`, `--key product` or `--key positions` says how to combine their positions
+    let mut dividers: Vec<Value> = Vec::new();
+    let mut style = KeyStyle::Product;
+    for (idx, arg) in args.iter().enumerate() {
+        if arg == "--divider" {
+            dividers.push(parse_packet(args.get(idx + 1).ok_or("--divider needs a packet")?)?);
+        } else if arg == "--key" {
+            style = args.get(idx + 1).ok_or("--key needs sum, product or positions")?.parse()?;
         }
-        if first <= second {
-            sum += idx as i32 + 1;
+    }
+    if dividers.is_empty() {
+        dividers = vec![parse_packet("[[2]]")?, parse_packet("[[6]]")?];
+    }
+    // `--explain N` shows how the Nth pair of packets compares
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--explain") {
+        let pair_num: usize = args.get(flag_idx + 1).ok_or("--explain needs a pair number")?.parse()?;
+        let pairs = parse_pairs(&read_input()?)?;
+        let (left, right) = pairs.get(pair_num.wrapping_sub(1)).ok_or("there's no pair with that number")?;
+        print!("== Pair {} ==\n{}", pair_num, compare::explain_cmp(left, right));
+        return Ok(());
+    }
+
+    // `--sort FILE` sorts the packets in FILE, even if it's too big to fit in memory, holding
+    // `--chunk-size N` packets in memory at a time, and `--stream FILE` finds the decoder key for
+    // FILE without sorting it
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--sort" || arg == "--stream") {
+        let path = args.get(flag_idx + 1).ok_or("expected a filename of packets")?;
+        let reader = BufReader::new(File::open(path)?);
+        if args[flag_idx] == "--sort" {
+            let chunk_size: usize = match args.iter().position(|arg| arg == "--chunk-size") {
+                Some(idx) => args.get(idx + 1).ok_or("--chunk-size needs a number")?.parse()?,
+                None => 100_000,
+            };
+            extsort::external_sort(reader, io::stdout().lock(), chunk_size, &env::temp_dir())?;
+        } else {
+            let positions = extsort::divider_positions(reader, &dividers)?;
+            println!("Decoder key: {}", style.decoder_key(&positions)?);
         }
+        return Ok(());
     }
-    sum
-}
 
-fn find_divider_packets(input: &str) -> i32 {
-    let dividers: Vec<Value> = vec![
-        parse_value_exact("[[2]]"),
-        parse_value_exact("[[6]]")
-    ];
-
-    let (_, mut packets) = input_list_parser(input).unwrap();
-    packets.extend(dividers.clone());
-    packets.sort();
-    let mut decoder_key: i32 = 1;
-    for (i, packet) in packets.iter().enumerate() {
-        if packet == &dividers[0] || packet == &dividers[1] {
-            decoder_key *= (i + 1) as i32;
+    // `--generate N` writes N pairs of random packets, in the same format as the input. The
+    // packets can be changed with `--seed S`, `--depth D` (how deeply lists are nested),
+    // `--width W` (how long lists are) and `--max-int M`.
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--generate") {
+        let num_pairs: usize = args.get(flag_idx + 1).ok_or("--generate needs a number of pairs")?.parse()?;
+        let option = |name: &str, default: u64| -> Result<u64, Box<dyn Error>> {
+            match args.iter().position(|arg| arg == name) {
+                Some(idx) => Ok(args.get(idx + 1).ok_or("expected a number")?.parse()?),
+                None => Ok(default),
+            }
+        };
+        let mut packets = PacketGen::new(option("--seed", 13)?)
+            .depth(u32::try_from(option("--depth", 4)?)?)?
+            .width(usize::try_from(option("--width", 4)?)?)?
+            .ints(0..=i32::try_from(option("--max-int", 10)?)?)?;
+        let mut out = io::BufWriter::new(io::stdout().lock());
+        for pair_idx in 0..num_pairs {
+            if pair_idx > 0 {
+                writeln!(out)?;
+            }
+            writeln!(out, "{}\n{}", packets.packet(), packets.packet())?;
         }
+        return Ok(());
     }
-    decoder_key
-}
 
+    // `--from-json FILE` reads a JSON list of packets from FILE and writes them in the puzzle's
+    // format
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--from-json") {
+        let path = args.get(flag_idx + 1).ok_or("--from-json needs a filename")?;
+        match fs::read_to_string(path)?.parse::<Json>()? {
+            Json::Array(items) => {
+                for item in items.iter() {
+                    println!("{}", Value::try_from(item)?);
+                }
+            }
+            _ => return Err(JsonError::NotAPacket("expected a list of packets".to_owned()).into()),
+        }
+        return Ok(());
+    }
 
-fn main() {
-    let input = fs::read_to_string("input.txt").unwrap();
-    println!("Sum of ordered packets: {}", sum_ordered(&input));
-    println!("Decoder key: {}", find_divider_packets(&input));
+    let input = read_input()?;
+    println!("Sum of ordered packets: {}", sum_ordered(&input)?);
+    println!("Decoder key: {}", find_divider_packets(&input, &dividers, style)?);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -154,8 +212,8 @@ mod tests {
 
     #[test]
     fn test_pair() {
-        let (i, (first, second)) = input_pair_parser(TEST_PAIR).unwrap();
-        dbg!(i, first.clone(), second.clone());
+        let pairs = parse_pairs(TEST_PAIR).unwrap();
+        let (first, second) = pairs[0].clone();
         assert!(first < second);
         assert!(second > first);
         assert!(first == first);
@@ -163,18 +221,131 @@ mod tests {
 
     #[test]
     fn test_list() {
-        let (i, values) = input_list_parser(TEST_INPUT).unwrap();
+        let values = parse_packets(TEST_INPUT).unwrap();
         assert_eq!(values.len(), 16);
-        assert_eq!(i.len(), 0);
     }
 
     #[test]
     fn test_example() {
-        assert_eq!(sum_ordered(TEST_INPUT), 13);
+        assert_eq!(sum_ordered(TEST_INPUT), Ok(13));
     }
 
     #[test]
-    fn test_example_part2() {
-        assert_eq!(find_divider_packets(TEST_INPUT), 140);
+    fn test_example_part2() -> Result<(), Box<dyn Error>> {
+        let dividers = vec![parse_packet("[[2]]")?, parse_packet("[[6]]")?];
+        assert_eq!(find_divider_packets(TEST_INPUT, &dividers, KeyStyle::Product)?, DecoderKey::Number(140));
+        assert_eq!(
+            find_divider_packets(TEST_INPUT, &dividers, KeyStyle::Positions)?,
+            DecoderKey::Positions(vec![10, 14])
+        );
+        // a divider that's also in the input doesn't have a clear position
+        let dividers = vec![parse_packet("[[2]]")?, parse_packet("[[8,7,6]]")?];
+        let err = find_divider_packets(TEST_INPUT, &dividers, KeyStyle::Product).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "packet 6 is equal to divider [[8,7,6]], so the divider's position is ambiguous"
+        );
+        Ok(())
+    }
+
+    /// The way comparison used to work, by copying both sides into new lists.
+    fn cmp_by_copying(left: &Value, right: &Value) -> Ordering {
+        fn to_array(value: &Value) -> Vec<Value> {
+            match value {
+                Value::Int(n) => vec![Value::Int(*n)],
+                Value::Array(arr) => arr.clone(),
+            }
+        }
+        if let (Value::Int(left_int), Value::Int(right_int)) = (left, right) {
+            return left_int.cmp(right_int);
+        }
+        let (left, right) = (to_array(left), to_array(right));
+        for (left_item, right_item) in left.iter().zip(right.iter()) {
+            match cmp_by_copying(left_item, right_item) {
+                Ordering::Equal => continue,
+                unequal => return unequal,
+            }
+        }
+        left.len().cmp(&right.len())
+    }
+
+    #[test]
+    fn test_display() {
+        for line in TEST_INPUT.lines().filter(|line| !line.is_empty()) {
+            assert_eq!(parse_packet(line).unwrap().to_string(), line);
+        }
+        assert_eq!(parse_packet(" [ -1 ,[ ] ] ").unwrap().to_string(), "[-1,[]]");
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let packets = PacketGen::new(1213).depth(5).unwrap().ints(-1000..=1000).unwrap();
+        for packet in packets.take(1000) {
+            let text = packet.to_string();
+            let parsed = parse_packet(&text).unwrap();
+            assert_eq!(parsed, packet);
+            assert_eq!(parsed.to_string(), text);
+
+            let json: Json = Json::from(&packet).to_string().parse().unwrap();
+            assert_eq!(Value::try_from(&json), Ok(packet));
+        }
+    }
+
+    /// Lots of values that are nearly the same, so that comparisons have to look deep into
+    /// them, along with some values that the puzzle's comparison can't tell apart.
+    fn tricky_values(count: usize) -> Vec<Value> {
+        let packets = PacketGen::new(2022).depth(3).unwrap().width(3).unwrap();
+        let mut packets = packets.ints(0..=3).unwrap();
+        let mut values: Vec<Value> = (0..count).map(|_| packets.value_or_packet()).collect();
+        for text in ["2", "[2]", "[[2]]", "[[[2]]]", "[2,[]]", "[[2],[]]", "[]", "[[]]"] {
+            values.push(parse_packet(text).unwrap());
+        }
+        values
+    }
+
+    #[test]
+    fn test_cmp_matches_copying() {
+        let values = tricky_values(300);
+        for left in &values {
+            for right in &values {
+                let expected = cmp_by_copying(left, right);
+                assert_eq!(compare::packet_cmp(left, right), expected, "{} vs {}", left, right);
+                // the full ordering only differs when breaking ties
+                if expected != Ordering::Equal {
+                    assert_eq!(left.cmp(right), expected, "{} vs {}", left, right);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_int_vs_list() {
+        // the puzzle can't tell these apart, but they aren't the same
+        let (int, list) = (Value::Int(2), Value::Array(vec![Value::Int(2)]));
+        assert_ne!(int, list);
+        assert_eq!(compare::packet_cmp(&int, &list), Ordering::Equal);
+        assert_eq!(int.cmp(&list), Ordering::Less);
+        assert_eq!(list.cmp(&int), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_ordering_laws() {
+        let values = tricky_values(60);
+        for a in &values {
+            for b in &values {
+                let ab = a.cmp(b);
+                assert_eq!(ab, b.cmp(a).reverse(), "antisymmetry: {} vs {}", a, b);
+                assert_eq!(ab == Ordering::Equal, a == b, "consistent with ==: {} vs {}", a, b);
+                for c in &values {
+                    if ab != Ordering::Greater && b.cmp(c) != Ordering::Greater {
+                        assert_ne!(a.cmp(c), Ordering::Greater, "transitivity: {} <= {} <= {}", a, b, c);
+                    }
+                }
+            }
+        }
+        // and sorting agrees with comparing
+        let mut sorted = values.clone();
+        sorted.sort();
+        assert!(sorted.windows(2).all(|pair| pair[0] <= pair[1]));
     }
 }
@@ -0,0 +1,283 @@
+//! Parsing packets, with errors that say where the problem is and what should have been there.
+//!
+//! A packet is an int or a list of packets, like `[1,[2,-3],[]]`, on a line by itself. Spaces
+//! and tabs are allowed between the parts of a packet. In the puzzle input, packets come in
+//! pairs, with a blank line between pairs.
+use std::error::Error;
+use std::fmt;
+
+use nom::{
+    branch::alt,
+    character::complete::{char, digit1, one_of, space0},
+    combinator::{eof, map, opt, recognize},
+    error::ErrorKind,
+    sequence::pair,
+    IResult,
+};
+
+use crate::Value;
+
+/// A syntax error found by nom, with the input that was left when it happened.
+#[derive(Debug)]
+struct SyntaxError<'a> {
+    rest: &'a str,
+    expected: String,
+}
+
+impl<'a> nom::error::ParseError<&'a str> for SyntaxError<'a> {
+    fn from_error_kind(input: &'a str, kind: ErrorKind) -> Self {
+        let expected = match kind {
+            ErrorKind::Digit => "a digit",
+            ErrorKind::Eof => "the end of the packet",
+            _ => "something else",
+        };
+        SyntaxError { rest: input, expected: expected.to_owned() }
+    }
+
+    fn from_char(input: &'a str, ch: char) -> Self {
+        SyntaxError { rest: input, expected: format!("'{}'", ch) }
+    }
+
+    fn append(_input: &'a str, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// When all the alternatives fail, report the one that got the furthest.
+    fn or(self, other: Self) -> Self {
+        if other.rest.len() < self.rest.len() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+type PResult<'a, O> = IResult<&'a str, O, SyntaxError<'a>>;
+
+/// If `parser` fails without getting anywhere, say that we expected `expected`. Errors from
+/// further into the input are more specific, so they're left alone, and so are failures, which
+/// already know exactly what's wrong.
+fn expect<'a, O>(
+    expected: &'static str,
+    mut parser: impl FnMut(&'a str) -> PResult<'a, O>,
+) -> impl FnMut(&'a str) -> PResult<'a, O> {
+    move |i| {
+        parser(i).map_err(|err| match err {
+            nom::Err::Error(err) if err.rest.len() == i.len() => {
+                nom::Err::Error(SyntaxError { rest: i, expected: expected.to_owned() })
+            }
+            err => err,
+        })
+    }
+}
+
+fn int(i: &str) -> PResult<'_, i32> {
+    let (rest, digits) = recognize(pair(opt(char('-')), digit1))(i)?;
+    match digits.parse() {
+        Ok(n) => Ok((rest, n)),
+        Err(_) => Err(nom::Err::Failure(SyntaxError {
+            rest: i,
+            expected: "a number that fits in 32 bits".to_owned(),
+        })),
+    }
+}
+
+fn array(i: &str) -> PResult<'_, Vec<Value>> {
+    let (i, _) = char('[')(i)?;
+    let (rest, _) = space0(i)?;
+    if let Ok((rest, _)) = char::<_, SyntaxError>(']')(rest) {
+        return Ok((rest, Vec::new()));
+    }
+    let mut items = Vec::new();
+    let mut i = i;
+    loop {
+        let (rest, item) = value(i)?;
+        items.push(item);
+        let (rest, separator) = expect("',' or ']'", one_of(",]"))(rest)?;
+        i = rest;
+        if separator == ']' {
+            return Ok((i, items));
+        }
+    }
+}
+
+// parse either an int or an array, with optional spaces around it
+fn value(i: &str) -> PResult<'_, Value> {
+    let (i, _) = space0(i)?;
+    let (i, value) = expect(
+        "'[' or a number",
+        alt((map(array, Value::Array), map(int, Value::Int))),
+    )(i)?;
+    let (i, _) = space0(i)?;
+    Ok((i, value))
+}
+
+/// A problem with a packet. Lines and columns count from 1, and so do packets.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PacketError {
+    pub line: usize,
+    pub column: usize,
+    /// Which packet in the file has the problem.
+    pub packet: usize,
+    pub expected: String,
+}
+
+impl fmt::Display for PacketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "packet {} (line {}, column {}): expected {}",
+            self.packet, self.line, self.column, self.expected
+        )
+    }
+}
+
+impl Error for PacketError {}
+
+/// Parse one packet, and nothing else. `line` and `packet` say where it is in the file, for
+/// error messages.
+pub fn parse_line(text: &str, line: usize, packet: usize) -> Result<Value, PacketError> {
+    let result = value(text).and_then(|(rest, value)| {
+        let (rest, _) = expect("the end of the packet", eof)(rest)?;
+        Ok((rest, value))
+    });
+    match result {
+        Ok((_, value)) => Ok(value),
+        Err(nom::Err::Error(err)) | Err(nom::Err::Failure(err)) => {
+            let offset = text.len() - err.rest.len();
+            Err(PacketError {
+                line,
+                column: text[..offset].chars().count() + 1,
+                packet,
+                expected: err.expected,
+            })
+        }
+        Err(nom::Err::Incomplete(_)) => unreachable!("complete parsers don't ask for more input"),
+    }
+}
+
+/// Parse a single packet, like `[[2]]`.
+pub fn parse_packet(text: &str) -> Result<Value, PacketError> {
+    parse_line(text, 1, 1)
+}
+
+/// Parse all the packets in a file, ignoring blank lines.
+pub fn parse_packets(input: &str) -> Result<Vec<Value>, PacketError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .enumerate()
+        .map(|(packet_idx, (line_idx, text))| parse_line(text, line_idx + 1, packet_idx + 1))
+        .collect()
+}
+
+/// Parse a file of packets in pairs, where each pair is two lines and there's a blank line
+/// between pairs.
+pub fn parse_pairs(input: &str) -> Result<Vec<(Value, Value)>, PacketError> {
+    let mut pairs = Vec::new();
+    // the first packet of a pair we're in the middle of
+    let mut first: Option<Value> = None;
+    // whether we just finished a pair, and haven't had a blank line since
+    let mut pair_just_ended = false;
+    let mut packet = 0;
+    let mut num_lines = 0;
+    for (line_idx, text) in input.lines().enumerate() {
+        let line = line_idx + 1;
+        num_lines = line;
+        let missing = |expected: &str, packet: usize| PacketError {
+            line,
+            column: 1,
+            packet,
+            expected: expected.to_owned(),
+        };
+        if text.trim().is_empty() {
+            if first.is_some() {
+                return Err(missing("a second packet", packet + 1));
+            }
+            pair_just_ended = false;
+            continue;
+        }
+        packet += 1;
+        if pair_just_ended {
+            return Err(missing("a blank line between pairs", packet));
+        }
+        let value = parse_line(text, line, packet)?;
+        match first.take() {
+            None => first = Some(value),
+            Some(first_value) => {
+                pairs.push((first_value, value));
+                pair_just_ended = true;
+            }
+        }
+    }
+    if first.is_some() {
+        let expected = "a second packet".to_owned();
+        return Err(PacketError { line: num_lines + 1, column: 1, packet: packet + 1, expected });
+    }
+    Ok(pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TEST_INPUT;
+
+    fn error_at(text: &str) -> (usize, String) {
+        let err = parse_packet(text).unwrap_err();
+        (err.column, err.expected)
+    }
+
+    #[test]
+    fn test_packets() {
+        assert_eq!(parse_packet("[]"), Ok(Value::Array(vec![])));
+        assert_eq!(
+            parse_packet(" [ 1 , [-2,\t[] ] ] "),
+            Ok(Value::Array(vec![
+                Value::Int(1),
+                Value::Array(vec![Value::Int(-2), Value::Array(vec![])]),
+            ]))
+        );
+        assert_eq!(parse_packet("-7"), Ok(Value::Int(-7)));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(error_at("[1,2"), (5, "',' or ']'".to_owned()));
+        assert_eq!(error_at("[1,,2]"), (4, "'[' or a number".to_owned()));
+        assert_eq!(error_at("[1]]"), (4, "the end of the packet".to_owned()));
+        assert_eq!(error_at("[1] x"), (5, "the end of the packet".to_owned()));
+        assert_eq!(error_at("[-]"), (3, "a digit".to_owned()));
+        assert_eq!(error_at("[99999999999]"), (2, "a number that fits in 32 bits".to_owned()));
+        assert_eq!(error_at(""), (1, "'[' or a number".to_owned()));
+        assert_eq!(error_at("[é,x]"), (2, "'[' or a number".to_owned()));
+        assert_eq!(
+            parse_packet("[1 2]").unwrap_err().to_string(),
+            "packet 1 (line 1, column 4): expected ',' or ']'"
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        assert_eq!(parse_pairs(TEST_INPUT).unwrap().len(), 8);
+        assert_eq!(parse_packets(TEST_INPUT).unwrap().len(), 16);
+        // trailing newlines and Windows line endings are fine
+        let crlf = format!("{}\n\n", TEST_INPUT.replace('\n', "\r\n"));
+        assert_eq!(parse_pairs(&crlf).unwrap().len(), 8);
+
+        let broken = TEST_INPUT.replace("[[8,7,6]]", "[[8,7,6]");
+        let err = PacketError { line: 8, column: 9, packet: 6, expected: "',' or ']'".to_owned() };
+        assert_eq!(parse_pairs(&broken), Err(err.clone()));
+        assert_eq!(parse_packets(&broken), Err(err));
+    }
+
+    #[test]
+    fn test_pairing_errors() {
+        let missing = PacketError { line: 2, column: 1, packet: 2, expected: "a second packet".to_owned() };
+        assert_eq!(parse_pairs("[1]\n\n[2]\n[3]"), Err(missing));
+        let at_end = PacketError { line: 5, column: 1, packet: 4, expected: "a second packet".to_owned() };
+        assert_eq!(parse_pairs("[1]\n[2]\n\n[3]"), Err(at_end));
+        let extra = PacketError { line: 3, column: 1, packet: 3, expected: "a blank line between pairs".to_owned() };
+        assert_eq!(parse_pairs("[1]\n[2]\n[3]\n[4]"), Err(extra));
+    }
+}
@@ -0,0 +1,105 @@
+//! Timing the directory sizes on a made-up transcript with very deep directories.
+//!
+//! Adding up the size of every directory separately means a file deep in the tree gets added
+//! once for every directory above it, so the work grows with the square of the depth. This
+//! compares that against adding up all the sizes in one pass.
+use std::error::Error;
+use std::time::Instant;
+
+use crate::shell::Shell;
+use crate::FilesystemEntry;
+
+/// A transcript that goes `depth` directories down, listing `files` files in each one, and then
+/// comes back up with `cd ..`. The sizes are arbitrary but always the same.
+pub fn deep_transcript(depth: usize, files: usize) -> String {
+    let mut lines = vec!["$ cd /".to_owned()];
+    for level in 0..depth {
+        lines.push("$ ls".to_owned());
+        let deeper = level + 1 < depth;
+        if deeper {
+            lines.push("dir sub".to_owned());
+        }
+        for file_idx in 0..files {
+            let size = (level * 7919 + file_idx * 104_729) % 50_000 + 1;
+            lines.push(format!("{} file{}.txt", size, file_idx));
+        }
+        if deeper {
+            lines.push("$ cd sub".to_owned());
+        }
+    }
+    for _ in 1..depth {
+        lines.push("$ cd ..".to_owned());
+    }
+    lines.join("\n")
+}
+
+/// Part 1 the way it used to be done, with `size` called for each directory.
+fn naive_size_of_small_dirs(filesystem: &FilesystemEntry, cutoff: i64) -> i64 {
+    filesystem.depth_first_dirs().iter().map(|dir| dir.size()).filter(|&size| size <= cutoff).sum()
+}
+
+/// Part 2 the way it used to be done.
+fn naive_size_of_dir_to_delete(filesystem: &FilesystemEntry, max_size: i64) -> i64 {
+    let min_size = filesystem.size() - max_size;
+    filesystem.depth_first_dirs().iter().map(|dir| dir.size()).filter(|&size| size >= min_size).min().unwrap()
+}
+
+/// Replay a transcript `depth` directories deep, and time both ways of answering the puzzle.
+pub fn run(depth: usize) -> Result<(), Box<dyn Error>> {
+    let transcript = deep_transcript(depth, 3);
+    let start = Instant::now();
+    let filesystem = Shell::replay(&transcript)?;
+    println!("replayed {} lines in {:?}", transcript.lines().count(), start.elapsed());
+
+    let start = Instant::now();
+    let naive = (
+        naive_size_of_small_dirs(&filesystem, 100_000),
+        naive_size_of_dir_to_delete(&filesystem, 40_000_000),
+    );
+    println!("sizing each directory separately: {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let one_pass = (filesystem.size_of_small_dirs(100_000), filesystem.size_of_dir_to_delete(40_000_000));
+    println!("sizing all directories in one pass: {:?}", start.elapsed());
+
+    if naive != one_pass {
+        return Err(format!("the answers don't agree: {:?} vs {:?}", naive, one_pass).into());
+    }
+    println!("answers: {} and {}", one_pass.0, one_pass.1);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{read_dir_tree, TEST_INPUT};
+
+    #[test]
+    fn test_deep_transcript() {
+        assert_eq!(deep_transcript(2, 1), "$ cd /\n$ ls\ndir sub\n1 file0.txt\n$ cd sub\n$ ls\n7920 file0.txt\n$ cd ..");
+        let filesystem = Shell::replay(&deep_transcript(100, 3)).unwrap();
+        assert_eq!(filesystem.depth_first_dirs().len(), 100);
+    }
+
+    #[test]
+    fn test_same_answers() {
+        let example = read_dir_tree(TEST_INPUT).unwrap();
+        let deep = Shell::replay(&deep_transcript(100, 3)).unwrap();
+        for filesystem in [example, deep] {
+            for cutoff in [0, 100_000, 1_000_000, i64::MAX] {
+                assert_eq!(filesystem.size_of_small_dirs(cutoff), naive_size_of_small_dirs(&filesystem, cutoff));
+            }
+            for max_size in [0, 40_000_000, 100_000_000] {
+                assert_eq!(
+                    filesystem.size_of_dir_to_delete(max_size),
+                    naive_size_of_dir_to_delete(&filesystem, max_size)
+                );
+            }
+        }
+        // every directory in the deep transcript comes with its size
+        let deep = Shell::replay(&deep_transcript(20, 2)).unwrap();
+        for (dir, size) in deep.depth_first_dir_sizes() {
+            assert_eq!(dir.size(), size);
+        }
+    }
+}
@@ -1,9 +1,16 @@
 // An example to build from each day
+use std::env;
 use std::fs;
 use std::error::Error;
 use std::collections::HashMap;
 use parse_display::{Display, FromStr};
 
+mod bench;
+mod render;
+mod shell;
+use render::Units;
+use shell::Shell;
+
 pub const TEST_INPUT: &str = "$ cd /
 $ ls
 dir a
@@ -34,6 +41,8 @@ $ ls
 enum ElfShellLine {
     #[display("$ ls")]
     ListFiles,
+    #[display("$ pwd")]
+    PrintDir,
     #[display("$ cd {destination}")]
     ChangeDir { destination: String },
     #[display("dir {name}")]
@@ -51,34 +60,13 @@ enum FilesystemEntry {
 }
 
 impl FilesystemEntry {
-    /// In the shell session, we've seen a file at a particular path, and we know its size.
-    /// Put it into the filesystem in that path, creating more directories if necessary.
-    fn put_in_path(&mut self, path: &[String], filename: &str, size: i64) {
-        match self {
-            FilesystemEntry::File { size: _ } => {
-                panic!("did we cd into a file?");
-            },
-            FilesystemEntry::Dir { contents } => {
-                if path.len() == 0 {
-                    contents.insert(filename.to_owned(), FilesystemEntry::File { size });
-                } else {
-                    let path_component: &str = &path[0];
-                    if !(contents.contains_key(path_component)) {
-                        contents.insert(
-                            path_component.to_owned(), 
-                            FilesystemEntry::Dir { contents: HashMap::new() }
-                        );
-                    }
-                    match contents.get_mut(path_component) {
-                        Some(entry) => {
-                            entry.put_in_path(&path[1..], filename, size);
-                        },
-                        None => {
-                            panic!("I swear we just added this directory");
-                        }
-                    }
-                }
-            }
+    /// Look up the entry at a path, given as a list of names. The empty path is this entry.
+    #[cfg(test)]
+    fn get(&self, path: &[String]) -> Option<&FilesystemEntry> {
+        match (self, path.split_first()) {
+            (_, None) => Some(self),
+            (FilesystemEntry::Dir { contents }, Some((name, rest))) => contents.get(name)?.get(rest),
+            (FilesystemEntry::File { size: _ }, Some(_)) => None,
         }
     }
 
@@ -94,89 +82,108 @@ impl FilesystemEntry {
 
     /// A list of directories in this filesystem, found recursively in depth-first order.
     fn depth_first_dirs(&self) -> Vec<&FilesystemEntry> {
+        self.depth_first_dir_sizes().into_iter().map(|(dir, _)| dir).collect()
+    }
+
+    /// Like `depth_first_dirs`, but with the size of each directory too. The sizes all get
+    /// added up in one pass, from the bottom up, instead of calling `size` on each directory,
+    /// which would add up a deep directory's files once for every directory above it.
+    fn depth_first_dir_sizes(&self) -> Vec<(&FilesystemEntry, i64)> {
         let mut dirs = Vec::new();
+        self.collect_dir_sizes(&mut dirs);
+        dirs
+    }
+
+    /// Push this entry and the directories in it onto `dirs`, if it's a directory, and return
+    /// its size.
+    fn collect_dir_sizes<'a>(&'a self, dirs: &mut Vec<(&'a FilesystemEntry, i64)>) -> i64 {
         match self {
-            FilesystemEntry::File { size: _ } => dirs,
+            FilesystemEntry::File { size } => *size,
             FilesystemEntry::Dir { contents } => {
-                dirs.push(&self);
-                for entry in contents.values() {
-                    dirs.extend(entry.depth_first_dirs().iter());
-                }
-                dirs
+                // we don't know our size until we've seen everything inside us, so fill it in
+                // afterward
+                let idx = dirs.len();
+                dirs.push((self, 0));
+                let size = contents.values().map(|entry| entry.collect_dir_sizes(dirs)).sum();
+                dirs[idx].1 = size;
+                size
             }
         }
     }
 
     /// An implementation of part 1: find all the small enough directories and add up their size.
     fn size_of_small_dirs(&self, cutoff: i64) -> i64 {
-        match self {
-            FilesystemEntry::File { size: _ } => 0,
-            FilesystemEntry::Dir { contents: _ } => {
-                let mut total: i64 = 0;
-                for dir in self.depth_first_dirs() {
-                    if dir.size() <= cutoff {
-                        total += dir.size();
-                    }
-                }
-                total
-            }
-        }
+        self.depth_first_dir_sizes().into_iter().map(|(_, size)| size).filter(|&size| size <= cutoff).sum()
     }
 
-    /// Used in part 2: find the smallest directory with size at least min_size.
-    fn find_dir_to_delete(&self, min_size: i64) -> &FilesystemEntry {
-        let mut dir_to_delete: Option<&FilesystemEntry> = None;
-        let mut smallest_size: i64 = i64::MAX;
-        for dir in self.depth_first_dirs() {
-            let size = dir.size();
-            if size >= min_size && size < smallest_size {
-                dir_to_delete = Some(dir);
-                smallest_size = size;
+    /// Find the smallest directory in `dir_sizes` with size at least min_size, along with its
+    /// size.
+    fn smallest_dir_at_least(
+        dir_sizes: Vec<(&FilesystemEntry, i64)>,
+        min_size: i64,
+    ) -> (&FilesystemEntry, i64) {
+        let mut dir_to_delete: Option<(&FilesystemEntry, i64)> = None;
+        for (dir, size) in dir_sizes {
+            if size >= min_size && dir_to_delete.is_none_or(|(_, smallest_size)| size < smallest_size) {
+                dir_to_delete = Some((dir, size));
             }
         }
         dir_to_delete.unwrap()
     }
 
+    /// Find the smallest directory with size at least min_size. Part 2 only needs its size,
+    /// which `size_of_dir_to_delete` gets from the same pass that sizes the root.
+    #[cfg(test)]
+    fn find_dir_to_delete(&self, min_size: i64) -> &FilesystemEntry {
+        FilesystemEntry::smallest_dir_at_least(self.depth_first_dir_sizes(), min_size).0
+    }
+
     /// Implementation of part 2: we need the size of the filesystem to be no more than
     /// max_size. Find the smallest directory to delete that will make this work.
     fn size_of_dir_to_delete(&self, max_size: i64) -> i64 {
-        let min_size: i64 = self.size() - max_size;
-        self.find_dir_to_delete(min_size).size()
+        // the root comes first, and its size is the size of everything
+        let dir_sizes = self.depth_first_dir_sizes();
+        let min_size = dir_sizes[0].1 - max_size;
+        FilesystemEntry::smallest_dir_at_least(dir_sizes, min_size).1
     }
 }
 
 
 /// Read the elf's shell session and build a filesystem based on what we see.
 fn read_dir_tree(input: &str) -> Result<FilesystemEntry, Box<dyn Error>> {
-    let mut path: Vec<String> = Vec::new();
-    let mut filesystem = FilesystemEntry::Dir { contents: HashMap::new() };
-    for line in input.lines() {
-        let shell: ElfShellLine = line.parse()?;
-        match shell {
-            ElfShellLine::ChangeDir { destination } => {
-                if destination == ".." {
-                    path.pop();
-                } else if destination == "/" {
-                    path.drain(..);
-                } else {
-                    path.push(destination.clone());
-                }
-            },
-            ElfShellLine::FileEntry { name, size } => {
-                filesystem.put_in_path(&path, &name, size);
-            },
-            // We don't do anything with the 'dir' entries -- we'll create the directories when we
-            // see a file inside them.
-            ElfShellLine::DirEntry { name: _ } => {},
-            ElfShellLine::ListFiles => {}
-        }
-    }
-    Ok(filesystem)
+    Ok(Shell::replay(input)?)
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    // `--bench DEPTH` times the directory sizes on a made-up transcript that goes DEPTH
+    // directories deep
+    if let Some(flag_idx) = args.iter().position(|arg| arg == "--bench") {
+        let depth: usize = args.get(flag_idx + 1).ok_or("--bench needs a depth")?.parse()?;
+        return bench::run(depth);
+    }
+
     let input = fs::read_to_string("input.txt")?;
     let filesystem = read_dir_tree(&input)?;
+
+    // `--tree` draws the whole filesystem, and `--du` lists the size of each directory. `du`
+    // can be limited to directories `--max-depth N` levels down, and `--human` writes the sizes
+    // like `du -h`.
+    if args.iter().any(|arg| arg == "--tree") {
+        print!("{}", filesystem);
+        return Ok(());
+    }
+    if args.iter().any(|arg| arg == "--du") {
+        let max_depth = match args.iter().position(|arg| arg == "--max-depth") {
+            Some(idx) => Some(args.get(idx + 1).ok_or("--max-depth needs a number")?.parse()?),
+            None => None,
+        };
+        let units = if args.iter().any(|arg| arg == "--human") { Units::Human } else { Units::Bytes };
+        print!("{}", render::du(&filesystem, max_depth, units));
+        return Ok(());
+    }
+
     println!("sum of small directories: {}", filesystem.size_of_small_dirs(100_000));
     // We can subtract numbers. We need room for a 30 MB thing on a 70 MB filesystem, so the rest of
     // the filesystem has a max size of 40 MB.
@@ -192,6 +199,8 @@ mod tests {
     fn test_parse_lines() {
         let sh: ElfShellLine = "$ ls".parse().unwrap();
         assert_eq!(sh, ElfShellLine::ListFiles);
+        let sh: ElfShellLine = "$ pwd".parse().unwrap();
+        assert_eq!(sh, ElfShellLine::PrintDir);
         let sh: ElfShellLine = "$ cd here".parse().unwrap();
         assert_eq!(sh, ElfShellLine::ChangeDir { destination: "here".to_owned() });
         let sh: ElfShellLine = "123 filename".parse().unwrap();
@@ -201,6 +210,11 @@ mod tests {
     #[test]
     fn test_full_parse() {
         let filesystem = read_dir_tree(TEST_INPUT).unwrap();
+        assert_eq!(filesystem.size(), 48381165);
+        let path = ["a".to_owned(), "e".to_owned(), "i".to_owned()];
+        assert!(matches!(filesystem.get(&path), Some(FilesystemEntry::File { size: 584 })));
+        assert!(filesystem.get(&path[..2]).is_some());
+        assert!(filesystem.get(&["a".to_owned(), "x".to_owned()]).is_none());
     }
 
     #[test]
@@ -213,5 +227,8 @@ mod tests {
     fn test_example_2() {
         let filesystem = read_dir_tree(TEST_INPUT).unwrap();
         assert_eq!(filesystem.size_of_dir_to_delete(40_000_000), 24933642);
+        // that's the size of d
+        let dir = filesystem.find_dir_to_delete(48381165 - 40_000_000);
+        assert!(std::ptr::eq(dir, filesystem.get(&["d".to_owned()]).unwrap()));
     }
 }
@@ -0,0 +1,174 @@
+//! Showing the filesystem we rebuilt, either as a tree like the puzzle draws it, or as a list
+//! of directory sizes like `du` prints.
+use std::fmt;
+
+use parse_display::{Display, FromStr};
+
+use crate::FilesystemEntry;
+
+/// Write an entry and everything in it, indented by its depth, with names in sorted order.
+fn write_tree(f: &mut fmt::Formatter, name: &str, entry: &FilesystemEntry, depth: usize) -> fmt::Result {
+    match entry {
+        FilesystemEntry::File { size } => {
+            writeln!(f, "{:indent$}- {} (file, size={})", "", name, size, indent = depth * 2)
+        }
+        FilesystemEntry::Dir { contents } => {
+            writeln!(f, "{:indent$}- {} (dir)", "", name, indent = depth * 2)?;
+            let mut names: Vec<&String> = contents.keys().collect();
+            names.sort();
+            for name in names {
+                write_tree(f, name, &contents[name], depth + 1)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Shows the filesystem the way the puzzle does, assuming this entry is the root.
+impl fmt::Display for FilesystemEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_tree(f, "/", self, 0)
+    }
+}
+
+/// How `du` writes sizes.
+#[derive(Display, FromStr, PartialEq, Eq, Debug, Clone, Copy)]
+#[display(style = "lowercase")]
+pub enum Units {
+    /// Just the number.
+    Bytes,
+    /// Like `du -h`: powers of 1024, with a suffix and at most 3 digits.
+    Human,
+}
+
+impl Units {
+    pub fn format(&self, size: i64) -> String {
+        match self {
+            Units::Bytes => size.to_string(),
+            Units::Human => human_size(size),
+        }
+    }
+}
+
+/// Write a size like `du -h` does. It rounds up, so that a size never looks smaller than it
+/// is, and it uses one decimal place for numbers under 10.
+fn human_size(size: i64) -> String {
+    const SUFFIXES: [&str; 6] = ["", "K", "M", "G", "T", "P"];
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut value = size as f64;
+    let mut suffix_idx = 0;
+    while value >= 1024.0 && suffix_idx < SUFFIXES.len() - 1 {
+        value /= 1024.0;
+        suffix_idx += 1;
+    }
+    let tenths = (value * 10.0).ceil() / 10.0;
+    if tenths < 10.0 {
+        return format!("{:.1}{}", tenths, SUFFIXES[suffix_idx]);
+    }
+    let whole = value.ceil();
+    if whole >= 1024.0 && suffix_idx < SUFFIXES.len() - 1 {
+        // rounding up took us to the next suffix
+        return format!("1.0{}", SUFFIXES[suffix_idx + 1]);
+    }
+    format!("{}{}", whole, SUFFIXES[suffix_idx])
+}
+
+/// A directory's path, its depth (the root is 0), and its recursive size.
+struct DuRow {
+    path: String,
+    depth: usize,
+    size: i64,
+}
+
+/// Add up the size of `entry` in one pass, and add a row for each directory in it that's no
+/// deeper than `max_depth`.
+fn du_rows(entry: &FilesystemEntry, path: &str, depth: usize, max_depth: Option<usize>, rows: &mut Vec<DuRow>) -> i64 {
+    match entry {
+        FilesystemEntry::File { size } => *size,
+        FilesystemEntry::Dir { contents } => {
+            let size = contents
+                .iter()
+                .map(|(name, inner)| {
+                    let inner_path = format!("{}/{}", path.trim_end_matches('/'), name);
+                    du_rows(inner, &inner_path, depth + 1, max_depth, rows)
+                })
+                .sum();
+            if max_depth.is_none_or(|max_depth| depth <= max_depth) {
+                rows.push(DuRow { path: path.to_owned(), depth, size });
+            }
+            size
+        }
+    }
+}
+
+/// List the recursive size of each directory, biggest first, like `du | sort -rn`. Directories
+/// more than `max_depth` levels below the root are left out, but still count toward the size of
+/// the directories above them.
+pub fn du(root: &FilesystemEntry, max_depth: Option<usize>, units: Units) -> String {
+    let mut rows = Vec::new();
+    du_rows(root, "/", 0, max_depth, &mut rows);
+    // ties go to the shallower directory, and then alphabetical order, so the output doesn't
+    // depend on the order of a HashMap
+    rows.sort_by(|a, b| b.size.cmp(&a.size).then(a.depth.cmp(&b.depth)).then_with(|| a.path.cmp(&b.path)));
+    rows.iter().map(|row| format!("{}\t{}\n", units.format(row.size), row.path)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{read_dir_tree, TEST_INPUT};
+
+    #[test]
+    fn test_tree() {
+        // from the puzzle text
+        let filesystem = read_dir_tree(TEST_INPUT).unwrap();
+        assert_eq!(
+            filesystem.to_string(),
+            "- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+"
+        );
+    }
+
+    #[test]
+    fn test_du() {
+        let filesystem = read_dir_tree(TEST_INPUT).unwrap();
+        assert_eq!(du(&filesystem, None, Units::Bytes), "48381165\t/\n24933642\t/d\n94853\t/a\n584\t/a/e\n");
+        assert_eq!(du(&filesystem, Some(1), Units::Bytes), "48381165\t/\n24933642\t/d\n94853\t/a\n");
+        assert_eq!(du(&filesystem, Some(0), Units::Human), "47M\t/\n");
+        assert_eq!(du(&filesystem, None, Units::Human), "47M\t/\n24M\t/d\n93K\t/a\n584\t/a/e\n");
+        assert_eq!("human".parse(), Ok(Units::Human));
+    }
+
+    #[test]
+    fn test_human_size() {
+        let cases = [
+            (0, "0"),
+            (1023, "1023"),
+            (1024, "1.0K"),
+            (1025, "1.1K"),
+            (10 * 1024 - 1, "10K"),
+            (94853, "93K"),
+            (1024 * 1024 - 1, "1.0M"),
+            (14848514, "15M"),
+            (3 << 30, "3.0G"),
+        ];
+        for (size, expected) in cases {
+            assert_eq!(human_size(size), expected, "{}", size);
+        }
+    }
+}
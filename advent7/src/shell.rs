@@ -0,0 +1,435 @@
+//! Replaying the elf's shell session, like a tiny shell would run it.
+//!
+//! The shell keeps track of the working directory and builds up the filesystem from what `ls`
+//! shows. `cd` takes absolute or relative paths with any number of components, including `.`
+//! and `..`, and it complains about directories that don't exist (or that we haven't seen yet),
+//! instead of making them up. `pwd` is also understood, and if the transcript shows what it
+//! printed, that has to match where we think we are.
+//!
+//! The same directory can be listed more than once. The listings get merged, and they have to
+//! agree: a file can't change size, a name can't be a file in one listing and a directory in
+//! another, and a listing can't leave out something an earlier one had. Those conflicts are
+//! errors that say which lines disagree.
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+use crate::{ElfShellLine, FilesystemEntry};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ShellErrorKind {
+    /// The line isn't a command or output that we understand.
+    Unparseable(String),
+    NoSuchDirectory(String),
+    NotADirectory(String),
+    /// `cd ..` when we're already at the root.
+    AboveRoot,
+    /// An output line that doesn't come after a command that would print it.
+    UnexpectedOutput(String),
+    /// `pwd` printed a different directory than the one we're in.
+    WrongDirectory { expected: String, found: String },
+    /// A file was listed with a different size on an earlier line.
+    SizeChanged { path: String, old_size: i64, old_line: usize, new_size: i64 },
+    /// The same path was listed as a file on one line and as a directory on another.
+    FileAndDir { path: String, file_line: usize, dir_line: usize },
+    /// A listing left out an entry that was seen on an earlier line.
+    MissingEntry { path: String, seen_line: usize },
+}
+
+/// Something that went wrong on a particular line (counting from 1) of the transcript.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ShellError {
+    pub line: usize,
+    pub kind: ShellErrorKind,
+}
+
+impl fmt::Display for ShellErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShellErrorKind::Unparseable(text) => write!(f, "can't understand {:?}", text),
+            ShellErrorKind::NoSuchDirectory(path) => write!(f, "cd: {}: no such directory", path),
+            ShellErrorKind::NotADirectory(path) => write!(f, "cd: {}: not a directory", path),
+            ShellErrorKind::AboveRoot => write!(f, "cd: can't go above /"),
+            ShellErrorKind::UnexpectedOutput(text) => {
+                write!(f, "{:?} looks like output, but no command printed it", text)
+            }
+            ShellErrorKind::WrongDirectory { expected, found } => {
+                write!(f, "pwd printed {}, but we're in {}", found, expected)
+            }
+            ShellErrorKind::SizeChanged { path, old_size, old_line, new_size } => write!(
+                f,
+                "{} has size {}, but it had size {} on line {}",
+                path, new_size, old_size, old_line
+            ),
+            ShellErrorKind::FileAndDir { path, file_line, dir_line } => write!(
+                f,
+                "{} is listed as a file on line {} and as a directory on line {}",
+                path, file_line, dir_line
+            ),
+            ShellErrorKind::MissingEntry { path, seen_line } => {
+                write!(f, "the listing is missing {}, which was seen on line {}", path, seen_line)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
+impl Error for ShellError {}
+
+/// Write a path the way `pwd` would.
+pub fn path_string(path: &[String]) -> String {
+    format!("/{}", path.join("/"))
+}
+
+/// What kind of output we expect from the last command.
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum Output {
+    Nothing,
+    /// The output of the `ls` on this line, and the names it has shown so far.
+    Listing { line: usize, names: HashSet<String> },
+    WorkingDir,
+}
+
+/// A file or directory that we've seen. The shell keeps these in a list, so that it can hold
+/// on to the working directory by its index, instead of following its path down from the root
+/// on every line.
+struct Node {
+    name: String,
+    /// The index of the directory this is in, or None for the root.
+    parent: Option<usize>,
+    /// The line where this was first listed, or 0 for the root.
+    line: usize,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    /// A directory, with the index of each thing in it.
+    Dir { contents: HashMap<String, usize> },
+    File { size: i64 },
+}
+
+pub struct Shell {
+    /// Everything we've seen, starting with the root. Things are only ever added, and they're
+    /// always added after the directory they're in.
+    nodes: Vec<Node>,
+    /// The index of the working directory.
+    cwd: usize,
+    output: Output,
+    /// The number of the line we're on.
+    line: usize,
+}
+
+impl Shell {
+    /// A shell in an empty filesystem, at the root.
+    pub fn new() -> Self {
+        let kind = NodeKind::Dir { contents: HashMap::new() };
+        let root = Node { name: String::new(), parent: None, line: 0, kind };
+        Shell { nodes: vec![root], cwd: 0, output: Output::Nothing, line: 0 }
+    }
+
+    /// Run a whole transcript, and return the filesystem it showed us.
+    pub fn replay(input: &str) -> Result<FilesystemEntry, ShellError> {
+        let mut shell = Shell::new();
+        for line in input.lines() {
+            shell.run_line(line)?;
+        }
+        shell.finish_listing()?;
+        Ok(shell.into_filesystem())
+    }
+
+    /// Run one line of the transcript, which is either a command or some output from the last
+    /// command.
+    pub fn run_line(&mut self, text: &str) -> Result<(), ShellError> {
+        self.line += 1;
+        if text.starts_with('$') {
+            // a new command means the last one is done printing
+            self.finish_listing()?;
+        }
+        let line = self.line;
+        self.run(text).map_err(|kind| ShellError { line, kind })
+    }
+
+    /// The working directory, the way `pwd` would print it.
+    pub fn pwd(&self) -> String {
+        path_string(&self.names_to(self.cwd))
+    }
+
+    fn run(&mut self, text: &str) -> Result<(), ShellErrorKind> {
+        // the output of pwd is a path, which we wouldn't be able to parse as anything else
+        if self.output == Output::WorkingDir && !text.starts_with('$') {
+            self.output = Output::Nothing;
+            let expected = self.pwd();
+            if text != expected {
+                return Err(ShellErrorKind::WrongDirectory { expected, found: text.to_owned() });
+            }
+            return Ok(());
+        }
+        let parsed: ElfShellLine = text.parse().map_err(|_| ShellErrorKind::Unparseable(text.to_owned()))?;
+        match parsed {
+            ElfShellLine::ListFiles => self.output = Output::Listing { line: self.line, names: HashSet::new() },
+            ElfShellLine::PrintDir => self.output = Output::WorkingDir,
+            ElfShellLine::ChangeDir { destination } => {
+                self.cwd = self.resolve(&destination)?;
+                self.output = Output::Nothing;
+            }
+            ElfShellLine::DirEntry { name } => self.add_entry(text, name, None)?,
+            ElfShellLine::FileEntry { name, size } => self.add_entry(text, name, Some(size))?,
+        }
+        Ok(())
+    }
+
+    /// The names on the way from the root to a node.
+    fn names_to(&self, mut idx: usize) -> Vec<String> {
+        let mut names = Vec::new();
+        while let Some(parent) = self.nodes[idx].parent {
+            names.push(self.nodes[idx].name.clone());
+            idx = parent;
+        }
+        names.reverse();
+        names
+    }
+
+    /// The path of something called `name` in the directory `dir`, for error messages.
+    fn path_in(&self, dir: usize, name: &str) -> String {
+        let mut path = self.names_to(dir);
+        path.push(name.to_owned());
+        path_string(&path)
+    }
+
+    fn contents(&self, dir: usize) -> &HashMap<String, usize> {
+        match &self.nodes[dir].kind {
+            NodeKind::Dir { contents } => contents,
+            // cd checks that it goes to a directory, and ls only changes what's inside it
+            NodeKind::File { size: _ } => panic!("the working directory isn't a directory"),
+        }
+    }
+
+    /// Add a line of `ls` output to the working directory, where `size` is None for a
+    /// directory. If we've seen this entry before, it has to be the same as it was.
+    fn add_entry(&mut self, text: &str, name: String, size: Option<i64>) -> Result<(), ShellErrorKind> {
+        let names = match &mut self.output {
+            Output::Listing { line: _, names } => names,
+            _ => return Err(ShellErrorKind::UnexpectedOutput(text.to_owned())),
+        };
+        names.insert(name.clone());
+        let line = self.line;
+        let existing = match self.contents(self.cwd).get(&name) {
+            Some(&existing) => &self.nodes[existing],
+            None => {
+                let kind = match size {
+                    Some(size) => NodeKind::File { size },
+                    None => NodeKind::Dir { contents: HashMap::new() },
+                };
+                let idx = self.nodes.len();
+                self.nodes.push(Node { name: name.clone(), parent: Some(self.cwd), line, kind });
+                if let NodeKind::Dir { contents } = &mut self.nodes[self.cwd].kind {
+                    contents.insert(name, idx);
+                }
+                return Ok(());
+            }
+        };
+        let old_line = existing.line;
+        match (&existing.kind, size) {
+            (NodeKind::Dir { contents: _ }, None) => Ok(()),
+            (NodeKind::File { size: old_size }, Some(new_size)) if *old_size == new_size => Ok(()),
+            (NodeKind::File { size: old_size }, Some(new_size)) => Err(ShellErrorKind::SizeChanged {
+                path: self.path_in(self.cwd, &name),
+                old_size: *old_size,
+                old_line,
+                new_size,
+            }),
+            (NodeKind::File { size: _ }, None) => Err(ShellErrorKind::FileAndDir {
+                path: self.path_in(self.cwd, &name),
+                file_line: old_line,
+                dir_line: line,
+            }),
+            (NodeKind::Dir { contents: _ }, Some(_)) => Err(ShellErrorKind::FileAndDir {
+                path: self.path_in(self.cwd, &name),
+                file_line: line,
+                dir_line: old_line,
+            }),
+        }
+    }
+
+    /// If we were reading the output of `ls`, check that it showed everything that we already
+    /// knew was in the directory. The error is reported on the line of the `ls`.
+    fn finish_listing(&mut self) -> Result<(), ShellError> {
+        let (line, names) = match &self.output {
+            Output::Listing { line, names } => (*line, names),
+            _ => return Ok(()),
+        };
+        // if several things are missing, complain about the one we saw first
+        let missing = self
+            .contents(self.cwd)
+            .iter()
+            .filter(|(name, _)| !names.contains(*name))
+            .map(|(name, &idx)| (self.nodes[idx].line, name))
+            .min();
+        if let Some((seen_line, name)) = missing {
+            return Err(ShellError {
+                line,
+                kind: ShellErrorKind::MissingEntry { path: self.path_in(self.cwd, name), seen_line },
+            });
+        }
+        self.output = Output::Nothing;
+        Ok(())
+    }
+
+    /// Work out where `cd destination` would take us, one component at a time.
+    fn resolve(&self, destination: &str) -> Result<usize, ShellErrorKind> {
+        let mut dir = if destination.starts_with('/') { 0 } else { self.cwd };
+        for component in destination.split('/') {
+            match component {
+                "" | "." => {}
+                ".." => dir = self.nodes[dir].parent.ok_or(ShellErrorKind::AboveRoot)?,
+                name => match self.contents(dir).get(name) {
+                    Some(&idx) => match self.nodes[idx].kind {
+                        NodeKind::Dir { contents: _ } => dir = idx,
+                        NodeKind::File { size: _ } => {
+                            return Err(ShellErrorKind::NotADirectory(self.path_in(dir, name)))
+                        }
+                    },
+                    None => return Err(ShellErrorKind::NoSuchDirectory(self.path_in(dir, name))),
+                },
+            }
+        }
+        Ok(dir)
+    }
+
+    /// Turn what we've seen into a tree. Everything comes after the directory it's in, so going
+    /// backwards, each thing is finished by the time it gets put into its directory.
+    fn into_filesystem(self) -> FilesystemEntry {
+        let mut entries: Vec<Option<FilesystemEntry>> = self
+            .nodes
+            .iter()
+            .map(|node| match node.kind {
+                NodeKind::Dir { contents: _ } => Some(FilesystemEntry::Dir { contents: HashMap::new() }),
+                NodeKind::File { size } => Some(FilesystemEntry::File { size }),
+            })
+            .collect();
+        for (idx, node) in self.nodes.into_iter().enumerate().skip(1).rev() {
+            let entry = entries[idx].take().unwrap();
+            if let Some(FilesystemEntry::Dir { contents }) = &mut entries[node.parent.unwrap()] {
+                contents.insert(node.name, entry);
+            }
+        }
+        entries[0].take().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TEST_INPUT;
+
+    /// Replay the lines, and say what went wrong on which line.
+    fn replay_error(lines: &[&str]) -> (usize, ShellErrorKind) {
+        let err = Shell::replay(&lines.join("\n")).expect_err("the transcript should fail");
+        (err.line, err.kind)
+    }
+
+    #[test]
+    fn test_paths() {
+        let mut shell = Shell::new();
+        for line in TEST_INPUT.lines() {
+            shell.run_line(line).unwrap();
+        }
+        let mut cd = |destination: &str| {
+            shell.run_line(&format!("$ cd {}", destination)).map_err(|err| err.kind)?;
+            Ok::<String, ShellErrorKind>(shell.pwd())
+        };
+        assert_eq!(cd("/a/e"), Ok("/a/e".to_owned()));
+        assert_eq!(cd("../../d"), Ok("/d".to_owned()));
+        assert_eq!(cd("./../a/./e/"), Ok("/a/e".to_owned()));
+        assert_eq!(cd("/"), Ok("/".to_owned()));
+        assert_eq!(cd("a//e/.."), Ok("/a".to_owned()));
+        // a failed cd doesn't go anywhere
+        assert_eq!(cd("e/x"), Err(ShellErrorKind::NoSuchDirectory("/a/e/x".to_owned())));
+        assert_eq!(cd("/b.txt"), Err(ShellErrorKind::NotADirectory("/b.txt".to_owned())));
+        assert_eq!(cd("/a/f/.."), Err(ShellErrorKind::NotADirectory("/a/f".to_owned())));
+        assert_eq!(cd("../.."), Err(ShellErrorKind::AboveRoot));
+        assert_eq!(cd("."), Ok("/a".to_owned()));
+    }
+
+    #[test]
+    fn test_pwd() {
+        let transcript = ["$ pwd", "/", "$ ls", "dir a", "$ cd a", "$ pwd", "/a", "$ pwd", "$ cd /", "$ pwd"];
+        assert!(Shell::replay(&transcript.join("\n")).is_ok());
+        assert_eq!(
+            replay_error(&["$ ls", "dir a", "$ cd a", "$ pwd", "/"]),
+            (5, ShellErrorKind::WrongDirectory { expected: "/a".to_owned(), found: "/".to_owned() })
+        );
+        assert_eq!(
+            replay_error(&["$ pwd", "/", "/"]),
+            (3, ShellErrorKind::Unparseable("/".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(replay_error(&["$ cd /", "$ cd a"]), (2, ShellErrorKind::NoSuchDirectory("/a".to_owned())));
+        assert_eq!(replay_error(&["$ cd /", "$ cd .."]), (2, ShellErrorKind::AboveRoot));
+        assert_eq!(
+            replay_error(&["$ ls", "12 a", "$ cd a"]),
+            (3, ShellErrorKind::NotADirectory("/a".to_owned()))
+        );
+        assert_eq!(
+            replay_error(&["$ ls", "dir a", "$ cd a", "12 b"]),
+            (4, ShellErrorKind::UnexpectedOutput("12 b".to_owned()))
+        );
+        assert_eq!(replay_error(&["$ ls", "$ rm -rf /"]), (2, ShellErrorKind::Unparseable("$ rm -rf /".to_owned())));
+        let err = Shell::replay("$ cd /\n$ cd nope").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: cd: /nope: no such directory");
+    }
+
+    #[test]
+    fn test_empty_dirs() {
+        let filesystem = Shell::replay("$ ls\ndir a\n$ cd a\n$ ls\ndir b\n$ cd b\n$ ls").unwrap();
+        let path = ["a".to_owned(), "b".to_owned()];
+        assert!(matches!(filesystem.get(&path), Some(FilesystemEntry::Dir { contents }) if contents.is_empty()));
+    }
+
+    #[test]
+    fn test_repeated_listings() {
+        // listing everything a second time doesn't change anything
+        let again = format!("{}\n$ cd /\n$ ls\n8504156 c.dat\ndir d\n14848514 b.txt\ndir a", TEST_INPUT);
+        let filesystem = Shell::replay(&again).unwrap();
+        assert_eq!(filesystem.size(), 48381165);
+        assert_eq!(filesystem.size_of_small_dirs(100_000), 95437);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        assert_eq!(
+            replay_error(&["$ ls", "12 a", "dir b", "$ ls", "12 a", "13 a"]),
+            (
+                6,
+                ShellErrorKind::SizeChanged { path: "/a".to_owned(), old_size: 12, old_line: 2, new_size: 13 }
+            )
+        );
+        assert_eq!(
+            replay_error(&["$ ls", "12 a", "$ ls", "dir a"]),
+            (4, ShellErrorKind::FileAndDir { path: "/a".to_owned(), file_line: 2, dir_line: 4 })
+        );
+        assert_eq!(
+            replay_error(&["$ ls", "dir a", "$ cd /", "$ ls", "12 a"]),
+            (5, ShellErrorKind::FileAndDir { path: "/a".to_owned(), file_line: 5, dir_line: 2 })
+        );
+        // the missing entry is noticed when the listing ends, which can be at the end of the input
+        assert_eq!(
+            replay_error(&["$ ls", "dir a", "12 b", "$ ls", "12 b", "$ cd a"]),
+            (4, ShellErrorKind::MissingEntry { path: "/a".to_owned(), seen_line: 2 })
+        );
+        assert_eq!(
+            replay_error(&["$ ls", "dir a", "12 b", "$ ls"]),
+            (4, ShellErrorKind::MissingEntry { path: "/a".to_owned(), seen_line: 2 })
+        );
+        let err = Shell::replay("$ ls\n1 x\n$ ls\n2 x").unwrap_err();
+        assert_eq!(err.to_string(), "line 4: /x has size 2, but it had size 1 on line 2");
+    }
+}
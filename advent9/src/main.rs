@@ -53,8 +53,86 @@ fn make_rope(length: usize) -> Vec<Position> {
     vec![Position { x: 0, y: 0 }; length]
 }
 
-/// Move the rope and return the new position of the
-fn move_rope(rope: &mut Vec<Position>, mov: &RopeMove, visited: &mut HashSet<Position>) {
+/// A rule for how each knot follows the knot in front of it, after that knot has moved.
+trait FollowRule {
+    /// Given the position of the knot ahead (the leader) and the knot that's following it,
+    /// return the new position of the following knot.
+    fn follow(&self, leader: Position, knot: Position) -> Position;
+}
+
+/// The rule from the puzzle: if the knot is no longer touching its leader, it steps one unit
+/// toward the leader on each axis, which may be a diagonal step.
+#[derive(Debug, Clone, Copy, Default)]
+struct StepFollow;
+
+impl FollowRule for StepFollow {
+    fn follow(&self, leader: Position, knot: Position) -> Position {
+        SlackFollow { slack: 1 }.follow(leader, knot)
+    }
+}
+
+/// Like the puzzle's rule, but the rope between knots is `slack` units long, so a knot only
+/// moves when its leader is more than `slack` units away (by Chebyshev distance).
+#[derive(Debug, Clone, Copy)]
+struct SlackFollow {
+    slack: i64,
+}
+
+impl FollowRule for SlackFollow {
+    fn follow(&self, leader: Position, knot: Position) -> Position {
+        let dist = max((leader.x - knot.x).abs(), (leader.y - knot.y).abs());
+        if dist > self.slack {
+            Position {
+                x: knot.x + (leader.x - knot.x).signum(),
+                y: knot.y + (leader.y - knot.y).signum(),
+            }
+        } else {
+            knot
+        }
+    }
+}
+
+/// Knots can't move diagonally. A knot that isn't touching its leader steps one unit along the
+/// axis where it's furthest behind, preferring the x axis in a tie.
+#[derive(Debug, Clone, Copy, Default)]
+struct OrthogonalFollow;
+
+impl FollowRule for OrthogonalFollow {
+    fn follow(&self, leader: Position, knot: Position) -> Position {
+        let dx = leader.x - knot.x;
+        let dy = leader.y - knot.y;
+        if max(dx.abs(), dy.abs()) < 2 {
+            knot
+        } else if dx.abs() >= dy.abs() {
+            Position { x: knot.x + dx.signum(), y: knot.y }
+        } else {
+            Position { x: knot.x, y: knot.y + dy.signum() }
+        }
+    }
+}
+
+/// The rope is stretchy: a knot closes half the gap to its leader on each axis, rounding toward
+/// zero, so it never moves while it's touching.
+#[derive(Debug, Clone, Copy, Default)]
+struct ElasticFollow;
+
+impl FollowRule for ElasticFollow {
+    fn follow(&self, leader: Position, knot: Position) -> Position {
+        Position {
+            x: knot.x + (leader.x - knot.x) / 2,
+            y: knot.y + (leader.y - knot.y) / 2,
+        }
+    }
+}
+
+/// Move the rope according to one move of its head, using the given rule to pull each knot
+/// along, and record each position that the tail visits.
+fn move_rope(
+    rope: &mut [Position],
+    mov: &RopeMove,
+    rule: &dyn FollowRule,
+    visited: &mut HashSet<Position>,
+) {
     for _ in 0..mov.num {
         match mov.dir {
             Direction::U => rope[0].y -= 1,
@@ -63,47 +141,44 @@ fn move_rope(rope: &mut Vec<Position>, mov: &RopeMove, visited: &mut HashSet<Pos
             Direction::R => rope[0].x += 1,
         }
         for idx in 0..(rope.len() - 1) {
-            let head = rope[idx];
-            let link = &mut rope[idx + 1];
-            let dist = max(
-                (head.x - link.x).abs(),
-                (head.y - link.y).abs(),
-            );
-            if dist >= 2 {
-                if head.x > link.x {
-                    link.x += 1;
-                } else if head.x < link.x {
-                    link.x -= 1;
-                }
-                if head.y > link.y {
-                    link.y += 1;
-                } else if head.y < link.y {
-                    link.y -= 1;
-                }
-            }
+            rope[idx + 1] = rule.follow(rope[idx], rope[idx + 1]);
         }
         let tail = rope[rope.len() - 1];
         visited.insert(tail);
     }
 }
 
-fn count_tail_positions(input: &str, rope_length: usize) -> i64 {
+fn count_tail_positions(input: &str, rope_length: usize, rule: &dyn FollowRule) -> i64 {
     let mut visited: HashSet<Position> = HashSet::new();
     visited.insert(Position { x: 0, y: 0 });
 
     let mut rope = make_rope(rope_length);
 
-    for line in input.split("\n") {
+    for line in input.split('\n') {
         let mov: RopeMove = line.parse().unwrap();
-        move_rope(&mut rope, &mov, &mut visited)
+        move_rope(&mut rope, &mov, rule, &mut visited)
     }
     visited.len() as i64
 }
 
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
-    println!("tail positions visited with length 2: {}", count_tail_positions(&input, 2));
-    println!("tail positions visited with length 10: {}", count_tail_positions(&input, 10));
+    println!("tail positions visited with length 2: {}", count_tail_positions(&input, 2, &StepFollow));
+    println!("tail positions visited with length 10: {}", count_tail_positions(&input, 10, &StepFollow));
+
+    // compare what happens under different rope physics
+    let variants: Vec<(&str, Box<dyn FollowRule>)> = vec![
+        ("orthogonal", Box::new(OrthogonalFollow)),
+        ("slack 2", Box::new(SlackFollow { slack: 2 })),
+        ("elastic", Box::new(ElasticFollow)),
+    ];
+    for (name, rule) in &variants {
+        println!(
+            "tail positions visited with length 10 and {} physics: {}",
+            name,
+            count_tail_positions(&input, 10, rule.as_ref())
+        );
+    }
 }
 
 #[cfg(test)]
@@ -123,12 +198,43 @@ mod tests {
 
     #[test]
     fn test_example() {
-        assert_eq!(count_tail_positions(TEST_INPUT, 2), 13);
-        assert_eq!(count_tail_positions(TEST_INPUT, 10), 1);
+        assert_eq!(count_tail_positions(TEST_INPUT, 2, &StepFollow), 13);
+        assert_eq!(count_tail_positions(TEST_INPUT, 10, &StepFollow), 1);
     }
 
     #[test]
     fn test_bigger_example() {
-        assert_eq!(count_tail_positions(BIGGER_TEST_INPUT, 10), 36);
+        assert_eq!(count_tail_positions(BIGGER_TEST_INPUT, 10, &StepFollow), 36);
+    }
+
+    #[test]
+    fn test_follow_rules() {
+        let leader = Position { x: 2, y: 1 };
+        let knot = Position { x: 0, y: 0 };
+        assert_eq!(StepFollow.follow(leader, knot), Position { x: 1, y: 1 });
+        assert_eq!(OrthogonalFollow.follow(leader, knot), Position { x: 1, y: 0 });
+        assert_eq!(ElasticFollow.follow(leader, knot), Position { x: 1, y: 0 });
+        assert_eq!(SlackFollow { slack: 2 }.follow(leader, knot), knot);
+
+        let far_leader = Position { x: 6, y: -4 };
+        assert_eq!(ElasticFollow.follow(far_leader, knot), Position { x: 3, y: -2 });
+        assert_eq!(SlackFollow { slack: 2 }.follow(far_leader, knot), Position { x: 1, y: -1 });
+    }
+
+    #[test]
+    fn test_compare_rules() {
+        // a slack of 1 is the puzzle's rule
+        for rope_length in [2, 10] {
+            assert_eq!(
+                count_tail_positions(BIGGER_TEST_INPUT, rope_length, &SlackFollow { slack: 1 }),
+                count_tail_positions(BIGGER_TEST_INPUT, rope_length, &StepFollow)
+            );
+        }
+        // a two-knot rope only differs from the puzzle's rule in how the tail turns corners
+        assert_eq!(count_tail_positions("R 4\nU 4", 2, &OrthogonalFollow), 7);
+        assert_eq!(count_tail_positions("R 4\nU 4", 2, &StepFollow), 7);
+        // with more slack, the tail falls further behind and visits fewer places
+        assert_eq!(count_tail_positions(TEST_INPUT, 2, &SlackFollow { slack: 3 }), 3);
+        assert_eq!(count_tail_positions("R 10", 2, &ElasticFollow), 10);
     }
 }